@@ -0,0 +1,2500 @@
+use crate::get_full_path_name;
+use crate::init_com_scoped_with;
+use crate::sysinfoapi;
+use crate::winuser;
+use crate::CWideStr;
+use crate::CWideString;
+use crate::CoTaskMemWideStringExt;
+use crate::ComGuard;
+use crate::ComInit;
+use crate::DisplayNameType;
+use crate::FileDialog;
+use crate::FileDialogEvents;
+use crate::FileFilters;
+use crate::FileOpenDialog;
+use crate::FileSaveDialog;
+use crate::KnownFolder;
+use crate::NfdError;
+use crate::NulError;
+use crate::ShellItem;
+use skylight::CoTaskMemWideString;
+use skylight::HResult;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::ffi::OsStringExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::CO_E_NOTINITIALIZED;
+use winapi::um::minwinbase::SYSTEMTIME;
+use winapi::um::shobjidl::FILEOPENDIALOGOPTIONS;
+use winapi::um::shobjidl::FOS_ALLOWMULTISELECT;
+use winapi::um::shobjidl::FOS_CREATEPROMPT;
+use winapi::um::shobjidl::FOS_DONTADDTORECENT;
+use winapi::um::shobjidl::FOS_FORCEFILESYSTEM;
+use winapi::um::shobjidl::FOS_FORCESHOWHIDDEN;
+use winapi::um::shobjidl::FOS_HIDEPINNEDPLACES;
+use winapi::um::shobjidl::FOS_NOREADONLYRETURN;
+use winapi::um::shobjidl::FOS_NOTESTFILECREATE;
+use winapi::um::shobjidl::FOS_PATHMUSTEXIST;
+
+mod async_dialog;
+
+pub use self::async_dialog::AsyncDialog;
+pub use self::async_dialog::CancelHandle;
+
+/// The kind of dialog a [`FileDialogBuilder`] builds
+pub trait DialogMode {
+    /// The dialog type
+    type Dialog: Deref<Target = FileDialog>;
+
+    /// Whether a selected path must end in a filename
+    const REQUIRE_FILENAME: bool = false;
+
+    /// Make a new dialog.
+    fn create_dialog() -> Result<Self::Dialog, HResult>;
+}
+
+/// A type that can be used as the parent window of a dialog, with [`FileDialogBuilder::parent`]
+///
+/// This is implemented for `HWND`s, `isize` handles, and `Option`s of them.
+/// With the `raw-window-handle` feature, it is also implemented for `RawWindowHandle`s and references to windows that have one.
+pub trait IntoHwnd {
+    /// Get the window handle, or `None` for no parent.
+    ///
+    /// Returns [`NfdError::InvalidParent`] if this is not a Win32 window.
+    fn into_hwnd(self) -> Result<Option<HWND>, NfdError>;
+}
+
+impl IntoHwnd for HWND {
+    fn into_hwnd(self) -> Result<Option<HWND>, NfdError> {
+        if self.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(self))
+    }
+}
+
+impl IntoHwnd for isize {
+    fn into_hwnd(self) -> Result<Option<HWND>, NfdError> {
+        (self as HWND).into_hwnd()
+    }
+}
+
+impl<T> IntoHwnd for Option<T>
+where
+    T: IntoHwnd,
+{
+    fn into_hwnd(self) -> Result<Option<HWND>, NfdError> {
+        match self {
+            Some(parent) => parent.into_hwnd(),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl IntoHwnd for raw_window_handle::RawWindowHandle {
+    fn into_hwnd(self) -> Result<Option<HWND>, NfdError> {
+        match self {
+            raw_window_handle::RawWindowHandle::Win32(handle) => (handle.hwnd as HWND).into_hwnd(),
+            _ => Err(NfdError::InvalidParent),
+        }
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl<W> IntoHwnd for &W
+where
+    W: raw_window_handle::HasRawWindowHandle,
+{
+    fn into_hwnd(self) -> Result<Option<HWND>, NfdError> {
+        self.raw_window_handle().into_hwnd()
+    }
+}
+
+/// Marker for building a [`FileOpenDialog`]
+#[derive(Debug, Clone, Copy)]
+pub enum Open {}
+
+impl DialogMode for Open {
+    type Dialog = FileOpenDialog;
+
+    fn create_dialog() -> Result<Self::Dialog, HResult> {
+        FileOpenDialog::new()
+    }
+}
+
+/// Marker for building a [`FileSaveDialog`]
+#[derive(Debug, Clone, Copy)]
+pub enum Save {}
+
+impl DialogMode for Save {
+    type Dialog = FileSaveDialog;
+
+    const REQUIRE_FILENAME: bool = true;
+
+    fn create_dialog() -> Result<Self::Dialog, HResult> {
+        FileSaveDialog::new()
+    }
+}
+
+/// The calls a [`FileDialogBuilder`] makes to set up a dialog.
+///
+/// This is implemented for [`FileDialog`].
+/// Implement it yourself to test builder logic without showing a window.
+pub trait FileDialogBackend {
+    /// Set the folder used if there is no recently used folder.
+    fn set_default_folder(&self, path: &Path) -> Result<(), NfdError>;
+
+    /// Set the folder to open.
+    fn set_folder(&self, path: &Path) -> Result<(), NfdError>;
+
+    /// Set the file types.
+    fn set_filetypes(&self, filetypes: &FileFilters) -> Result<(), NfdError>;
+
+    /// Set the filename.
+    fn set_filename(&self, filename: &CWideStr) -> Result<(), NfdError>;
+
+    /// Set the extension to append to filenames entered without one.
+    fn set_default_extension(&self, extension: &CWideStr) -> Result<(), NfdError>;
+
+    /// Add a line of explanatory text at the top of the dialog.
+    fn set_banner_text(&self, text: &CWideStr) -> Result<(), NfdError>;
+
+    /// Set the title of the dialog window.
+    fn set_title(&self, title: &CWideStr) -> Result<(), NfdError>;
+
+    /// Set the label of the OK button.
+    fn set_ok_button_label(&self, label: &CWideStr) -> Result<(), NfdError>;
+
+    /// Update the default extension whenever the selected file type changes.
+    ///
+    /// `extensions` holds the extension to use for each file type, in order.
+    /// File types without an extension leave the default extension unchanged.
+    fn set_auto_default_extension(
+        &self,
+        extensions: Vec<Option<CWideString>>,
+    ) -> Result<(), NfdError>;
+
+    /// Prevent the user from navigating outside of the given folder.
+    fn restrict_to(&self, path: &Path) -> Result<(), NfdError>;
+
+    /// Select the first `len` wide chars of the filename box when the dialog opens.
+    fn select_filename_prefix(&self, len: usize) -> Result<(), NfdError>;
+
+    /// Add a known folder to the places in the navigation pane.
+    fn add_place_known(&self, folder: KnownFolder, top: bool) -> Result<(), NfdError>;
+
+    /// Set the given option flags, keeping the options that are already set.
+    fn add_options(&self, options: FILEOPENDIALOGOPTIONS) -> Result<(), NfdError>;
+
+    /// Clear the given option flags, keeping the other options that are already set.
+    fn remove_options(&self, options: FILEOPENDIALOGOPTIONS) -> Result<(), NfdError>;
+
+    /// Keep the dialog open if the filename does not match the spec of the selected file type.
+    ///
+    /// `specs` holds the spec of each file type, in order.
+    fn enforce_filter_match(&self, specs: Vec<CWideString>) -> Result<(), NfdError>;
+}
+
+impl FileDialogBackend for FileDialog {
+    fn set_default_folder(&self, path: &Path) -> Result<(), NfdError> {
+        let shell_item =
+            ShellItem::from_path(path).map_err(NfdError::context("SHCreateItemFromParsingName"))?;
+        FileDialog::set_default_folder(self, shell_item)
+            .map_err(NfdError::context("SetDefaultFolder"))
+    }
+
+    fn set_folder(&self, path: &Path) -> Result<(), NfdError> {
+        let shell_item =
+            ShellItem::from_path(path).map_err(NfdError::context("SHCreateItemFromParsingName"))?;
+        FileDialog::set_folder(self, shell_item).map_err(NfdError::context("SetFolder"))
+    }
+
+    fn set_filetypes(&self, filetypes: &FileFilters) -> Result<(), NfdError> {
+        FileDialog::set_filetypes(self, filetypes).map_err(NfdError::context("SetFileTypes"))
+    }
+
+    fn set_filename(&self, filename: &CWideStr) -> Result<(), NfdError> {
+        FileDialog::set_filename(self, filename).map_err(NfdError::context("SetFileName"))
+    }
+
+    fn set_default_extension(&self, extension: &CWideStr) -> Result<(), NfdError> {
+        FileDialog::set_default_extension(self, extension)
+            .map_err(NfdError::context("SetDefaultExtension"))
+    }
+
+    fn set_banner_text(&self, text: &CWideStr) -> Result<(), NfdError> {
+        let customize = self
+            .customize()
+            .map_err(NfdError::context("QueryInterface"))?;
+        customize
+            .add_text(BANNER_TEXT_ID, text)
+            .map_err(NfdError::context("AddText"))?;
+
+        // Not all versions of Windows support making text prominent.
+        // The text is still shown, just less visibly, so this is not fatal.
+        let prominent = customize.make_prominent(BANNER_TEXT_ID);
+        if prominent.is_err() {
+            debug!("MakeProminent failed: {:?}", prominent);
+        }
+
+        Ok(())
+    }
+
+    fn set_title(&self, title: &CWideStr) -> Result<(), NfdError> {
+        FileDialog::set_title(self, title).map_err(NfdError::context("SetTitle"))
+    }
+
+    fn set_ok_button_label(&self, label: &CWideStr) -> Result<(), NfdError> {
+        FileDialog::set_ok_button_label(self, label).map_err(NfdError::context("SetOkButtonLabel"))
+    }
+
+    fn set_auto_default_extension(
+        &self,
+        extensions: Vec<Option<CWideString>>,
+    ) -> Result<(), NfdError> {
+        self.advise(AutoDefaultExtension { extensions })
+            .map_err(NfdError::context("Advise"))?;
+
+        Ok(())
+    }
+
+    fn restrict_to(&self, path: &Path) -> Result<(), NfdError> {
+        let shell_item =
+            ShellItem::from_path(path).map_err(NfdError::context("SHCreateItemFromParsingName"))?;
+        self.set_navigation_root(shell_item)
+            .map_err(NfdError::context("SetNavigationRoot"))?;
+
+        // Pinned places would let the user jump outside of the root.
+        let options = self
+            .get_options()
+            .map_err(NfdError::context("GetOptions"))?;
+        self.set_options(options | FOS_HIDEPINNEDPLACES)
+            .map_err(NfdError::context("SetOptions"))?;
+
+        Ok(())
+    }
+
+    fn select_filename_prefix(&self, len: usize) -> Result<(), NfdError> {
+        self.advise(SelectFilenamePrefix {
+            len,
+            done: Cell::new(false),
+        })
+        .map_err(NfdError::context("Advise"))?;
+
+        Ok(())
+    }
+
+    fn add_place_known(&self, folder: KnownFolder, top: bool) -> Result<(), NfdError> {
+        let shell_item = ShellItem::from_known_folder(folder)
+            .map_err(NfdError::context("SHGetKnownFolderItem"))?;
+        self.add_place(&shell_item, top)
+            .map_err(NfdError::context("AddPlace"))
+    }
+
+    fn add_options(&self, options: FILEOPENDIALOGOPTIONS) -> Result<(), NfdError> {
+        let current = self
+            .get_options()
+            .map_err(NfdError::context("GetOptions"))?;
+        self.set_options(current | options)
+            .map_err(NfdError::context("SetOptions"))
+    }
+
+    fn remove_options(&self, options: FILEOPENDIALOGOPTIONS) -> Result<(), NfdError> {
+        let current = self
+            .get_options()
+            .map_err(NfdError::context("GetOptions"))?;
+        self.set_options(current & !options)
+            .map_err(NfdError::context("SetOptions"))
+    }
+
+    fn enforce_filter_match(&self, specs: Vec<CWideString>) -> Result<(), NfdError> {
+        self.advise(FilterMatch { specs })
+            .map_err(NfdError::context("Advise"))?;
+
+        Ok(())
+    }
+}
+
+/// The control id of the banner text
+const BANNER_TEXT_ID: u32 = 1;
+
+/// Events that update the default extension to match the selected file type
+struct AutoDefaultExtension {
+    extensions: Vec<Option<CWideString>>,
+}
+
+impl FileDialogEvents for AutoDefaultExtension {
+    fn on_type_change(&self, dialog: &FileDialog) {
+        let index = match dialog.get_filetype_index() {
+            Ok(index) => index,
+            Err(_e) => {
+                debug!("GetFileTypeIndex failed: {}", _e);
+                return;
+            }
+        };
+
+        let extension = (index as usize)
+            .checked_sub(1)
+            .and_then(|index| self.extensions.get(index))
+            .and_then(Option::as_ref);
+        if let Some(extension) = extension {
+            let result = dialog.set_default_extension(extension);
+            if result.is_err() {
+                debug!("SetDefaultExtension failed: {:?}", result);
+            }
+        }
+    }
+}
+
+/// Events that select the start of the filename box once the dialog opens
+struct SelectFilenamePrefix {
+    len: usize,
+    done: Cell<bool>,
+}
+
+impl FileDialogEvents for SelectFilenamePrefix {
+    fn on_folder_change(&self, dialog: &FileDialog) {
+        // This is called when the dialog opens, and again on every navigation.
+        if self.done.replace(true) {
+            return;
+        }
+
+        let window = match dialog.get_window() {
+            Ok(window) => window,
+            Err(_e) => {
+                debug!("GetWindow failed: {}", _e);
+                return;
+            }
+        };
+
+        match winuser::find_filename_edit(window) {
+            Some(edit) => {
+                if !winuser::post_select_text(edit, 0, self.len) {
+                    debug!("failed to post filename selection");
+                }
+            }
+            None => {
+                debug!("failed to find the filename box");
+            }
+        }
+    }
+}
+
+/// Events that keep the dialog open if the filename does not match the selected file type
+struct FilterMatch {
+    specs: Vec<CWideString>,
+}
+
+impl FileDialogEvents for FilterMatch {
+    fn on_file_ok(&self, dialog: &FileDialog) -> bool {
+        let index = match dialog.get_filetype_index() {
+            Ok(index) => index,
+            Err(_e) => {
+                debug!("GetFileTypeIndex failed: {}", _e);
+                return true;
+            }
+        };
+        let spec = match (index as usize)
+            .checked_sub(1)
+            .and_then(|index| self.specs.get(index))
+        {
+            Some(spec) => spec,
+            None => return true,
+        };
+
+        let filename = match dialog.get_filename() {
+            Ok(filename) => filename,
+            Err(_e) => {
+                debug!("GetFileName failed: {}", _e);
+                return true;
+            }
+        };
+
+        let matches = filename_matches_spec(
+            &filename.as_c_wide_str().to_string_lossy(),
+            &spec.to_string_lossy(),
+        );
+        if !matches {
+            debug!("rejected filename not matching '{:?}'", spec);
+        }
+
+        matches
+    }
+}
+
+/// Check if a filename has an extension allowed by a filter spec, like `*.png;*.jpg`.
+///
+/// Filenames without an extension match, since the default extension is appended to them.
+/// Patterns other than `*.ext`, like `*.*` or `data*.bin`, match every filename.
+/// Each `*.ext` pattern is matched as a suffix of the whole filename,
+/// so multi-part extensions like `*.tar.gz` work.
+/// Extensions are compared case-insensitively.
+fn filename_matches_spec(filename: &str, spec: &str) -> bool {
+    if Path::new(filename).extension().is_none() {
+        return true;
+    }
+    let filename = filename.to_lowercase();
+
+    spec.split(';')
+        .map(|pattern| pattern.trim())
+        .any(|pattern| match pattern.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') && !suffix.contains(&['*', '?'][..]) => {
+                filename.ends_with(&suffix.to_lowercase())
+            }
+            _ => true,
+        })
+}
+
+/// Get the extension of the first pattern of a filter spec, like `txt` for `*.txt;*.lbl`.
+///
+/// Returns `None` if the first pattern is not a plain `*.ext` pattern, like `*.*` or `data*.bin`.
+fn spec_default_extension(spec: &CWideStr) -> Option<CWideString> {
+    let pattern = spec.split(u16::from(b';')).next()?;
+    let pattern = trim_spaces(pattern);
+    let extension = pattern.strip_prefix(&[u16::from(b'*'), u16::from(b'.')][..])?;
+
+    let is_wildcard = |unit: &u16| *unit == u16::from(b'*') || *unit == u16::from(b'?');
+    if extension.is_empty() || extension.iter().any(is_wildcard) {
+        return None;
+    }
+
+    CWideString::new(extension.to_vec()).ok()
+}
+
+/// Expand the placeholders of a filename template.
+///
+/// See [`FileDialogBuilder::filename_template`].
+fn expand_filename_template(template: &str, now: &SYSTEMTIME, counter: u32) -> String {
+    let mut filename = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filename.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let result = match &rest[1..end] {
+            "date" => write!(
+                filename,
+                "{:04}-{:02}-{:02}",
+                now.wYear, now.wMonth, now.wDay
+            ),
+            "time" => write!(
+                filename,
+                "{:02}-{:02}-{:02}",
+                now.wHour, now.wMinute, now.wSecond
+            ),
+            "counter" => write!(filename, "{}", counter),
+            _ => {
+                filename.push_str(&rest[..=end]);
+                Ok(())
+            }
+        };
+        result.expect("failed to write to a String");
+        rest = &rest[end + 1..];
+    }
+    filename.push_str(rest);
+
+    filename
+}
+
+/// Trim ASCII spaces from both ends of a wide slice.
+fn trim_spaces(mut slice: &[u16]) -> &[u16] {
+    let space = u16::from(b' ');
+    while let Some((first, rest)) = slice.split_first() {
+        if *first != space {
+            break;
+        }
+        slice = rest;
+    }
+    while let Some((last, rest)) = slice.split_last() {
+        if *last != space {
+            break;
+        }
+        slice = rest;
+    }
+
+    slice
+}
+
+/// Builder for a [`FileOpenDialog`]
+pub type FileOpenDialogBuilder = FileDialogBuilder<Open>;
+
+/// Builder for a [`FileSaveDialog`]
+pub type FileSaveDialogBuilder = FileDialogBuilder<Save>;
+
+/// Builder for a file dialog.
+///
+/// Use the [`FileOpenDialogBuilder`] and [`FileSaveDialogBuilder`] aliases to pick the kind of dialog.
+#[derive(Clone)]
+pub struct FileDialogBuilder<M> {
+    /// Whether to init com
+    pub init_com: bool,
+
+    /// Path to open by default
+    pub default_path: Option<PathBuf>,
+
+    /// Path to open, regardless of past choices
+    pub path: Option<PathBuf>,
+
+    /// File types
+    pub filetypes: FileFilters<'static>,
+
+    /// Filename
+    pub filename: Option<OsString>,
+
+    /// A template to expand into the filename each time a dialog is built
+    pub filename_template: Option<String>,
+
+    /// The number of times the filename template was expanded
+    filename_counter: Cell<u32>,
+
+    /// Whether to add an "All Files" file type if no file types were added
+    pub implicit_all_files: bool,
+
+    /// An item to open the parent folder of and pre-fill the name of
+    pub select_item: Option<PathBuf>,
+
+    /// The maximum number of items that may be selected in a multiple selection.
+    ///
+    /// Only used by open dialogs.
+    pub max_selection: Option<usize>,
+
+    /// The extension to append to filenames typed without one.
+    ///
+    /// Only used by save dialogs.
+    pub default_extension: Option<OsString>,
+
+    /// Whether to update the default extension to match the selected file type.
+    ///
+    /// Only used by save dialogs.
+    pub auto_default_extension: bool,
+
+    /// A line of explanatory text shown at the top of the dialog
+    pub banner_text: Option<OsString>,
+
+    /// The title of the dialog window
+    pub title: Option<OsString>,
+
+    /// The label of the OK button
+    pub ok_button_label: Option<OsString>,
+
+    /// The owner window of the dialog
+    pub parent: Option<HWND>,
+
+    /// Whether the parent passed to [`Self::parent`] was not a Win32 window
+    invalid_parent: bool,
+
+    /// A folder the user may not navigate outside of
+    pub restrict_to: Option<PathBuf>,
+
+    /// Whether to select only the name of the filename, not its extension
+    pub select_filename_base: bool,
+
+    /// The concurrency model to init COM with on the current thread for each execution
+    pub thread_com: Option<ComInit>,
+
+    /// Known folders to add to the places in the navigation pane, and whether to add them at the top
+    pub places: Vec<(KnownFolder, bool)>,
+
+    /// Extra option flags to set, in addition to the defaults
+    pub options: FILEOPENDIALOGOPTIONS,
+
+    /// Whether to keep the dialog open if the filename does not match the selected file type.
+    ///
+    /// Only used by save dialogs.
+    pub enforce_filter_match: bool,
+
+    /// Whether to append the extension of the selected file type to the result if it does not have it.
+    ///
+    /// Only used by save dialogs.
+    pub ensure_extension: bool,
+
+    /// Whether to allow selecting paths in folders that do not exist yet, and create them afterwards
+    ///
+    /// Only used by save dialogs.
+    pub create_folders: bool,
+
+    /// Whether to open the default path even if Windows remembers a folder
+    pub force_default_folder: bool,
+
+    /// Extra configuration to run against the dialog after the other options are applied
+    pub configure: Vec<Rc<ConfigureFn>>,
+
+    mode: PhantomData<M>,
+}
+
+impl<M> FileDialogBuilder<M>
+where
+    M: DialogMode,
+{
+    /// Make a new [`FileDialogBuilder`].
+    pub fn new() -> Self {
+        FileDialogBuilder {
+            init_com: false,
+            default_path: None,
+            path: None,
+            filetypes: FileFilters::new(),
+            filename: None,
+            filename_template: None,
+            filename_counter: Cell::new(0),
+            implicit_all_files: false,
+            select_item: None,
+            max_selection: None,
+            default_extension: None,
+            auto_default_extension: false,
+            banner_text: None,
+            title: None,
+            ok_button_label: None,
+            parent: None,
+            invalid_parent: false,
+            restrict_to: None,
+            select_filename_base: false,
+            thread_com: None,
+            places: Vec::new(),
+            options: 0,
+            enforce_filter_match: false,
+            ensure_extension: false,
+            create_folders: false,
+            force_default_folder: false,
+            configure: Vec::new(),
+            mode: PhantomData,
+        }
+    }
+
+    /// Whether to init com
+    pub fn init_com(&mut self) -> &mut Self {
+        self.init_com = true;
+        self
+    }
+
+    /// Init COM on the current thread for the duration of each execution, and uninit it afterwards.
+    ///
+    /// This lets a dialog be shown from a short-lived worker thread.
+    /// Unlike [`Self::init_com`], which inits COM on the thread and never uninits it,
+    /// every init is balanced by an uninit once the dialog is dropped.
+    /// If COM was already initialized on the thread with a different mode, executing fails.
+    /// This is only applied by the `execute` methods, not [`Self::build`],
+    /// since the dialog must be dropped before COM is uninitialized.
+    pub fn with_thread_com(&mut self, mode: ComInit) -> &mut Self {
+        self.thread_com = Some(mode);
+        self
+    }
+
+    /// Set the default path where the dialog will open
+    ///
+    /// This is only used the first time a dialog is shown.
+    /// After the user picks an item, Windows remembers its folder for this app and opens that instead,
+    /// ignoring the default path.
+    /// Use [`Self::force_default_folder`] or [`Self::path`] to always open in a folder.
+    pub fn default_path(&mut self, default_path: &Path) -> &mut Self {
+        self.default_path = Some(default_path.into());
+        self
+    }
+
+    /// Always open in [`Self::default_path`], even if Windows remembers a folder.
+    ///
+    /// This has no effect if [`Self::path`] or [`Self::select_item`] is set, since those take priority.
+    pub fn force_default_folder(&mut self) -> &mut Self {
+        self.force_default_folder = true;
+        self
+    }
+
+    /// Set the path where the dialog will open
+    ///
+    /// Unlike [`Self::default_path`], this is used even if Windows remembers a folder.
+    pub fn path(&mut self, path: &Path) -> &mut Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Set the default path where the dialog will open, without copying it.
+    ///
+    /// See [`Self::default_path`].
+    pub fn default_path_buf(&mut self, default_path: PathBuf) -> &mut Self {
+        self.default_path = Some(default_path);
+        self
+    }
+
+    /// Set the path where the dialog will open, without copying it.
+    ///
+    /// See [`Self::path`].
+    pub fn path_buf(&mut self, path: PathBuf) -> &mut Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Add a file type.
+    ///
+    /// # Panics
+    /// Panics if the name of filter contain an interior NUL.
+    pub fn filetype(&mut self, name: impl AsRef<OsStr>, filter: impl AsRef<OsStr>) -> &mut Self {
+        let name =
+            Cow::Owned(CWideString::new(name.as_ref()).expect("name contained an interior NUL"));
+        let filter = Cow::Owned(
+            CWideString::new(filter.as_ref()).expect("filter contained an interior NUL"),
+        );
+        self.filetypes.add_filter(name, filter);
+        self
+    }
+
+    /// Add a file type from wide chars that were already encoded.
+    ///
+    /// This avoids converting through an [`OsStr`] for callers that cache their wide filter data.
+    ///
+    /// # Errors
+    /// Returns an error if the name or filter contain an interior NUL.
+    pub fn filetype_raw(
+        &mut self,
+        name: Vec<u16>,
+        filter: Vec<u16>,
+    ) -> Result<&mut Self, NulError> {
+        let name = Cow::Owned(CWideString::new(name)?);
+        let filter = Cow::Owned(CWideString::new(filter)?);
+        self.filetypes.add_filter(name, filter);
+        Ok(self)
+    }
+
+    /// Add a file type for the given file extensions, like `png` and `jpg`.
+    ///
+    /// See [`FileFilters::add_extensions`].
+    /// Nothing is added if `exts` is empty.
+    ///
+    /// # Panics
+    /// Panics if an extension contains an interior NUL.
+    pub fn filetype_extensions(&mut self, exts: &[&str]) -> &mut Self {
+        self.filetypes
+            .add_extensions(exts, None)
+            .expect("extension contained an interior NUL");
+        self
+    }
+
+    /// Whether to add an "All Files" (`*.*`) file type if no other file types were added.
+    ///
+    /// This is off by default.
+    /// Enabling it changes the file type dropdown from empty to a single "All Files" entry.
+    pub fn implicit_all_files(&mut self, implicit_all_files: bool) -> &mut Self {
+        self.implicit_all_files = implicit_all_files;
+        self
+    }
+
+    /// Set the default filename
+    pub fn filename(&mut self, filename: impl AsRef<OsStr>) -> &mut Self {
+        self.filename = Some(filename.as_ref().into());
+        self
+    }
+
+    /// Show a line of explanatory text at the top of the dialog.
+    pub fn banner_text(&mut self, text: impl AsRef<OsStr>) -> &mut Self {
+        self.banner_text = Some(text.as_ref().into());
+        self
+    }
+
+    /// Set the title of the dialog window.
+    pub fn title(&mut self, title: impl AsRef<OsStr>) -> &mut Self {
+        self.title = Some(title.as_ref().into());
+        self
+    }
+
+    /// Set the label of the OK button, like "Open" or "Save".
+    pub fn ok_button_label(&mut self, label: impl AsRef<OsStr>) -> &mut Self {
+        self.ok_button_label = Some(label.as_ref().into());
+        self
+    }
+
+    /// Set the owner window of the dialog, making the dialog modal to it.
+    ///
+    /// This accepts any [`IntoHwnd`], like an `HWND`, an `isize` handle, or `None` to clear the parent.
+    /// The window must stay valid until the dialog is executed.
+    /// Invalid windows are not reported here; building the dialog fails with [`NfdError::InvalidParent`] instead.
+    pub fn parent(&mut self, parent: impl IntoHwnd) -> &mut Self {
+        match parent.into_hwnd() {
+            Ok(parent) => {
+                self.parent = parent;
+                self.invalid_parent = false;
+            }
+            Err(_e) => {
+                debug!("invalid parent: {}", _e);
+                self.parent = None;
+                self.invalid_parent = true;
+            }
+        }
+        self
+    }
+
+    /// Select only the name of the filename, not its extension, when the dialog opens.
+    ///
+    /// This lets the user type over the name while keeping the extension.
+    /// This is best-effort: it relies on the undocumented layout of the dialog,
+    /// so it may silently do nothing on some versions of Windows.
+    pub fn select_filename_base(&mut self) -> &mut Self {
+        self.select_filename_base = true;
+        self
+    }
+
+    /// Add a known folder to the places in the navigation pane, like [`KnownFolder::Downloads`].
+    ///
+    /// If `top` is true, the place is added above the pinned places instead of below them.
+    pub fn add_place_known(&mut self, folder: KnownFolder, top: bool) -> &mut Self {
+        self.places.push((folder, top));
+        self
+    }
+
+    /// Only allow items that have a file system path to be selected.
+    ///
+    /// Without this, the user may pick a virtual item, like a library or a phone,
+    /// and executing fails after the dialog closes because the item has no path.
+    pub fn force_filesystem(&mut self) -> &mut Self {
+        self.options |= FOS_FORCEFILESYSTEM;
+        self
+    }
+
+    /// Do not let the user select read-only items.
+    ///
+    /// Save dialogs already do this by default.
+    pub fn no_readonly_return(&mut self) -> &mut Self {
+        self.options |= FOS_NOREADONLYRETURN;
+        self
+    }
+
+    /// Do not check that the selected item can be created, which skips prompts about creating it.
+    ///
+    /// Without this, the dialog tries to create and then delete the item to check for errors, like a full disk.
+    pub fn no_test_file_create(&mut self) -> &mut Self {
+        self.options |= FOS_NOTESTFILECREATE;
+        self
+    }
+
+    /// Show hidden and system items, regardless of the user's Explorer settings.
+    ///
+    /// By default, the dialog follows Explorer, which hides them unless the user chose to show them.
+    /// This is useful for picking config files, which are often hidden.
+    pub fn show_hidden(&mut self) -> &mut Self {
+        self.options |= FOS_FORCESHOWHIDDEN;
+        self
+    }
+
+    /// Do not add the selected item to the user's recent items.
+    pub fn dont_add_to_recent(&mut self) -> &mut Self {
+        self.options |= FOS_DONTADDTORECENT;
+        self
+    }
+
+    /// Prevent the user from navigating outside of the given folder.
+    ///
+    /// This sets the navigation root and hides pinned places in the navigation pane.
+    /// The dialog opens in the folder unless [`Self::path`] or [`Self::select_item`] is set.
+    /// This requires Windows 7 or later; building the dialog fails on older versions.
+    pub fn restrict_to(&mut self, path: &Path) -> &mut Self {
+        self.restrict_to = Some(path.into());
+        self
+    }
+
+    /// Pre-fill the filename from a template, like `export_{date}.csv`.
+    ///
+    /// The placeholders are:
+    /// * `{date}`: the local date, like `2024-01-02`
+    /// * `{time}`: the local time, like `13-45-30`
+    /// * `{counter}`: the number of dialogs built from this template so far, starting at 1
+    ///
+    /// Other text, including unknown placeholders, is kept as is.
+    /// The template is expanded once per [`Self::build`], so each dialog gets a fresh date, time, and count.
+    /// This overrides [`Self::filename`], but is ignored if [`Self::select_item`] is set.
+    /// It is not applied by [`Self::apply`].
+    pub fn filename_template(&mut self, template: &str) -> &mut Self {
+        self.filename_template = Some(template.into());
+        self
+    }
+
+    /// Open the parent folder of the given path and pre-fill its filename.
+    ///
+    /// This overrides [`Self::path`] and [`Self::filename`].
+    pub fn select_item(&mut self, path: &Path) -> &mut Self {
+        self.select_item = Some(path.into());
+        self
+    }
+
+    /// Build a dialog.
+    ///
+    /// This does not apply [`Self::with_thread_com`].
+    pub fn build(&self) -> Result<M::Dialog, NfdError> {
+        let parent_is_valid = self.parent.map_or(true, winuser::is_window);
+        if self.invalid_parent || !parent_is_valid {
+            return Err(NfdError::InvalidParent);
+        }
+
+        if self.init_com {
+            skylight::init_mta_com_runtime().map_err(NfdError::context("CoInitializeEx"))?;
+            debug!("initialized com");
+        }
+
+        let dialog = M::create_dialog().map_err(create_dialog_error)?;
+        self.apply(&*dialog)?;
+
+        if self.select_item.is_none() {
+            if let Some(filename) = self.next_template_filename() {
+                let filename = CWideString::new(filename.as_str())?;
+                dialog
+                    .set_filename(&filename)
+                    .map_err(NfdError::context("SetFileName"))?;
+                debug!("set filename from template to '{:?}'", filename);
+            }
+        }
+
+        for (index, configure) in self.configure.iter().enumerate() {
+            configure(&dialog).map_err(|hr| NfdError::Configure { index, hr })?;
+        }
+
+        Ok(dialog)
+    }
+
+    /// Run extra configuration against the dialog when it is built, after the other options are applied.
+    ///
+    /// This is an escape hatch for settings this builder does not support,
+    /// like calling methods through [`FileDialog::as_raw`].
+    /// Closures run in the order they were added.
+    /// They take `Fn` instead of `FnOnce` since a builder may build many dialogs.
+    /// They are not run by [`Self::apply`].
+    ///
+    /// If a closure fails, [`Self::build`] returns [`NfdError::Configure`] with the index of that closure.
+    pub fn configure<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&FileDialog) -> Result<(), HResult> + 'static,
+    {
+        self.configure.push(Rc::new(f));
+        self
+    }
+
+    /// Apply the options of this builder to a backend.
+    ///
+    /// [`Self::build`] applies them to a real dialog.
+    /// Other backends can be used to inspect the calls a builder would make without showing a window.
+    pub fn apply(&self, backend: &dyn FileDialogBackend) -> Result<(), NfdError> {
+        if self.options != 0 {
+            backend.add_options(self.options)?;
+            debug!("added options 0x{:08X}", self.options);
+        }
+
+        if self.create_folders {
+            backend.remove_options(FOS_PATHMUSTEXIST | FOS_CREATEPROMPT)?;
+            debug!("allowed folders that do not exist");
+        }
+
+        if let Some(root) = self.restrict_to.as_deref() {
+            backend.restrict_to(root)?;
+
+            // Start inside the root unless a folder was chosen explicitly.
+            if self.path.is_none() && self.select_item.is_none() {
+                backend.set_folder(root)?;
+            }
+            debug!("restricted navigation to '{}'", root.display());
+        }
+
+        if let Some(default_path) = self.default_path.as_deref() {
+            backend.set_default_folder(default_path)?;
+            debug!("set default folder to '{}'", default_path.display());
+
+            // Setting the folder overrides any remembered folder.
+            if self.force_default_folder && self.path.is_none() && self.select_item.is_none() {
+                backend.set_folder(default_path)?;
+                debug!("forced default folder");
+            }
+        }
+
+        if let Some(path) = self.path.as_deref() {
+            backend.set_folder(path)?;
+            debug!("set folder to '{}'", path.display());
+        }
+
+        let filetypes = self.effective_filetypes();
+        if !filetypes.is_empty() {
+            backend.set_filetypes(&filetypes)?;
+            debug!("set {} file types", filetypes.len());
+
+            if self.enforce_filter_match {
+                backend.enforce_filter_match(filetypes.specs().map(ToOwned::to_owned).collect())?;
+                debug!("enforcing file type matches");
+            }
+        }
+
+        if let Some(filename) = self.filename.as_deref() {
+            let filename = CWideString::new(filename)?;
+            backend.set_filename(&filename)?;
+            debug!("set filename to '{:?}'", filename);
+        }
+
+        if let Some(item) = self.select_item.as_deref() {
+            let parent = item
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+            backend.set_folder(parent)?;
+
+            if let Some(filename) = item.file_name() {
+                let filename = CWideString::new(filename)?;
+                backend.set_filename(&filename)?;
+            }
+            debug!("selected item '{}'", item.display());
+        }
+
+        if self.select_filename_base {
+            let filename = self
+                .select_item
+                .as_deref()
+                .and_then(Path::file_name)
+                .or_else(|| self.filename.as_deref());
+            if let Some(stem) = filename.and_then(|filename| Path::new(filename).file_stem()) {
+                backend.select_filename_prefix(stem.encode_wide().count())?;
+                debug!("selecting filename base '{}'", stem.to_string_lossy());
+            }
+        }
+
+        if self.auto_default_extension && !self.filetypes.is_empty() {
+            let extensions: Vec<_> = self.filetypes.specs().map(spec_default_extension).collect();
+            if self.default_extension.is_none() {
+                if let Some(Some(extension)) = extensions.first() {
+                    backend.set_default_extension(extension)?;
+                }
+            }
+            backend.set_auto_default_extension(extensions)?;
+            debug!("enabled automatic default extension");
+        }
+
+        if let Some(default_extension) = self.default_extension.as_deref() {
+            let default_extension = CWideString::new(default_extension)?;
+            backend.set_default_extension(&default_extension)?;
+            debug!("set default extension to '{:?}'", default_extension);
+        }
+
+        if let Some(banner_text) = self.banner_text.as_deref() {
+            let banner_text = CWideString::new(banner_text)?;
+            backend.set_banner_text(&banner_text)?;
+            debug!("set banner text to '{:?}'", banner_text);
+        }
+
+        if let Some(title) = self.title.as_deref() {
+            let title = CWideString::new(title)?;
+            backend.set_title(&title)?;
+            debug!("set title to '{:?}'", title);
+        }
+
+        if let Some(ok_button_label) = self.ok_button_label.as_deref() {
+            let ok_button_label = CWideString::new(ok_button_label)?;
+            backend.set_ok_button_label(&ok_button_label)?;
+            debug!("set ok button label to '{:?}'", ok_button_label);
+        }
+
+        for &(folder, top) in &self.places {
+            backend.add_place_known(folder, top)?;
+            debug!("added place {:?}", folder);
+        }
+
+        Ok(())
+    }
+
+    /// Execute a dialog.
+    pub fn execute(&self) -> Result<PathBuf, NfdError> {
+        let _com_guard = self.init_thread_com()?;
+        let dialog = self.build()?;
+
+        show_for_path(&dialog, self.parent, self.result_checks())
+    }
+
+    /// Execute a dialog on a new thread, returning a future that resolves to the selected path.
+    ///
+    /// The dialog is shown from a single-threaded COM apartment on that thread,
+    /// so this does not block the current thread, and COM does not need to be initialized on it.
+    /// [`Self::init_com`] and [`Self::with_thread_com`] are ignored.
+    /// Call [`AsyncDialog::cancel`] or [`CancelHandle::cancel`] to close the dialog early.
+    ///
+    /// # Errors
+    /// The future resolves to [`NfdError::AsyncConfigure`] without showing a dialog
+    /// if closures were added with [`Self::configure`], since they can not be sent to another thread.
+    pub fn execute_async(&self) -> AsyncDialog
+    where
+        M: Clone + 'static,
+    {
+        if !self.configure.is_empty() {
+            return AsyncDialog::ready(Err(NfdError::AsyncConfigure));
+        }
+
+        let mut builder = self.clone();
+        builder.init_com = false;
+        builder.thread_com = None;
+
+        // Expand the template here, so the counter of this builder is updated.
+        if builder.select_item.is_none() {
+            if let Some(filename) = self.next_template_filename() {
+                builder.filename = Some(filename.into());
+            }
+        }
+        builder.filename_template = None;
+
+        AsyncDialog::spawn(builder)
+    }
+
+    /// Execute a dialog, returning a UTF-8 path.
+    ///
+    /// # Errors
+    /// Returns an error if the selected path is not valid UTF-8.
+    #[cfg(feature = "camino")]
+    pub fn execute_utf8(&self) -> Result<camino::Utf8PathBuf, NfdError> {
+        let path = self.execute()?;
+        camino::Utf8PathBuf::from_path_buf(path).map_err(NfdError::NonUtf8Path)
+    }
+
+    /// Execute a dialog, returning the path of the selected item in the given form.
+    ///
+    /// For example, [`DisplayNameType::DesktopAbsoluteParsing`] returns a UNC path for items on a network share,
+    /// and also works for items without a file system path.
+    /// [`Self::execute`] uses [`DisplayNameType::FileSysPath`].
+    pub fn execute_as(&self, display_type: DisplayNameType) -> Result<PathBuf, NfdError> {
+        let _com_guard = self.init_thread_com()?;
+        let dialog = self.build()?;
+        show(&dialog, self.parent)?;
+
+        // Only file system paths can be checked for a filename or used to create folders.
+        let checks = if display_type == DisplayNameType::FileSysPath {
+            self.result_checks()
+        } else {
+            ResultChecks::default()
+        };
+        let path = PathBuf::from(result_path(&dialog, display_type, checks)?.as_os_string());
+        debug!("dialog returned '{}'", path.display());
+
+        Ok(path)
+    }
+
+    /// Execute a dialog, returning details about the selected item.
+    pub fn execute_detailed(&self) -> Result<DialogResult, NfdError> {
+        let _com_guard = self.init_thread_com()?;
+        let dialog = self.build()?;
+        let path = show_for_path(&dialog, self.parent, self.result_checks())?;
+        let exists = path.exists();
+
+        Ok(DialogResult { path, exists })
+    }
+
+    /// Execute a dialog, writing the file system path of the selected item into `buf`.
+    ///
+    /// This avoids allocating a [`PathBuf`], which is useful when handing the path to C code.
+    /// `buf` is cleared first, then filled with the wide chars of the path followed by a NUL terminator.
+    /// `buf` is left empty if the dialog fails or is cancelled.
+    pub fn execute_into(&self, buf: &mut Vec<u16>) -> Result<(), NfdError> {
+        buf.clear();
+
+        let _com_guard = self.init_thread_com()?;
+        let dialog = self.build()?;
+        let path = show_for_display_name(&dialog, self.parent, self.result_checks())?;
+        buf.extend_from_slice(path.as_c_wide_str().as_slice_with_nul());
+
+        Ok(())
+    }
+
+    /// Execute a dialog, returning `None` if it was cancelled.
+    pub fn execute_optional(&self) -> Result<Option<PathBuf>, NfdError> {
+        Ok(self.execute_optional_detailed()?.path)
+    }
+
+    /// Execute a dialog, returning the selected path and file type even if it was cancelled.
+    pub fn execute_optional_detailed(&self) -> Result<DialogOutcome, NfdError> {
+        let _com_guard = self.init_thread_com()?;
+        let dialog = self.build()?;
+
+        debug!("showing dialog");
+        let shown = dialog
+            .show_optional(self.parent)
+            .map_err(NfdError::context("Show"))?;
+
+        // The file type index is valid even if the dialog was cancelled.
+        let filetype_index = dialog
+            .get_filetype_index()
+            .map_err(NfdError::context("GetFileTypeIndex"))?;
+
+        let path = if shown {
+            Some(result_file_path(&dialog, self.result_checks())?)
+        } else {
+            debug!("dialog was cancelled");
+            None
+        };
+
+        Ok(DialogOutcome {
+            path,
+            filetype_index,
+        })
+    }
+
+    /// Execute a dialog, opening it in and then updating a remembered folder.
+    ///
+    /// If `folder` is not empty, the dialog opens there, overriding [`Self::path`] and any folder Windows remembers.
+    /// After a successful selection, `folder` is replaced with the selected item's parent folder.
+    /// `folder` is left untouched if the dialog fails or is cancelled.
+    pub fn execute_remembering_folder(&self, folder: &mut PathBuf) -> Result<PathBuf, NfdError> {
+        let _com_guard = self.init_thread_com()?;
+        let dialog = self.build()?;
+
+        if !folder.as_os_str().is_empty() {
+            let shell_item = ShellItem::from_path(folder)
+                .map_err(NfdError::context("SHCreateItemFromParsingName"))?;
+            dialog
+                .set_folder(shell_item)
+                .map_err(NfdError::context("SetFolder"))?;
+            debug!("set remembered folder to '{}'", folder.display());
+        }
+
+        let path = show_for_path(&dialog, self.parent, self.result_checks())?;
+
+        if let Some(parent) = path.parent() {
+            *folder = parent.into();
+        }
+
+        Ok(path)
+    }
+
+    /// Get the configuration [`Self::apply`] would apply, without touching COM.
+    ///
+    /// This resolves options that interact, like [`Self::select_item`] overriding [`Self::filename`],
+    /// so configuration logic can be tested without a dialog.
+    /// Closures added with [`Self::configure`] are not included.
+    pub fn options_snapshot(&self) -> DialogConfig {
+        let select_parent = self.select_item.as_deref().map(|item| {
+            item.parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."))
+        });
+        let forced_default = self
+            .default_path
+            .as_deref()
+            .filter(|_| self.force_default_folder);
+        let folder = select_parent
+            .or_else(|| self.path.as_deref())
+            .or(forced_default)
+            .or_else(|| self.restrict_to.as_deref())
+            .map(PathBuf::from);
+
+        let filename = self
+            .select_item
+            .as_deref()
+            .and_then(Path::file_name)
+            .or_else(|| self.filename.as_deref())
+            .map(OsString::from);
+
+        let filetypes = self
+            .effective_filetypes()
+            .entries()
+            .map(|(name, spec)| {
+                (
+                    String::from_utf16_lossy(name.as_slice()),
+                    String::from_utf16_lossy(spec.as_slice()),
+                )
+            })
+            .collect();
+
+        let removed_options = if self.create_folders {
+            FOS_PATHMUSTEXIST | FOS_CREATEPROMPT
+        } else {
+            0
+        };
+
+        DialogConfig {
+            default_path: self.default_path.clone(),
+            folder,
+            restrict_to: self.restrict_to.clone(),
+            filetypes,
+            filename,
+            default_extension: self.default_extension.clone(),
+            auto_default_extension: self.auto_default_extension && !self.filetypes.is_empty(),
+            banner_text: self.banner_text.clone(),
+            title: self.title.clone(),
+            ok_button_label: self.ok_button_label.clone(),
+            places: self.places.clone(),
+            added_options: self.options,
+            removed_options,
+        }
+    }
+
+    /// Get the file types to set, including the implicit "All Files" file type.
+    fn effective_filetypes(&self) -> Cow<'_, FileFilters<'static>> {
+        if !self.filetypes.is_empty() || !self.implicit_all_files {
+            return Cow::Borrowed(&self.filetypes);
+        }
+
+        let mut filetypes = FileFilters::with_capacity(1);
+        filetypes
+            .add_extension("*", Some("All Files"))
+            .expect("\"All Files\" contains a NUL");
+        Cow::Owned(filetypes)
+    }
+
+    /// Expand the filename template for a new dialog, advancing the counter.
+    fn next_template_filename(&self) -> Option<String> {
+        let template = self.filename_template.as_deref()?;
+        let counter = self.filename_counter.get() + 1;
+        self.filename_counter.set(counter);
+
+        Some(expand_filename_template(
+            template,
+            &sysinfoapi::get_local_time(),
+            counter,
+        ))
+    }
+
+    /// Get what to check or do with the path of a dialog result.
+    fn result_checks(&self) -> ResultChecks<'_> {
+        ResultChecks {
+            require_filename: M::REQUIRE_FILENAME,
+            create_folders: self.create_folders,
+            ensure_extension: Some(&self.filetypes).filter(|_| self.ensure_extension),
+        }
+    }
+
+    /// Init COM on the current thread if [`Self::with_thread_com`] was used.
+    ///
+    /// The guard must outlive the dialog.
+    fn init_thread_com(&self) -> Result<Option<ComGuard>, NfdError> {
+        self.thread_com
+            .map(|mode| init_com_scoped_with(mode).map_err(NfdError::context("CoInitializeEx")))
+            .transpose()
+    }
+
+    /// Convert this into a builder for another kind of dialog, keeping the shared options.
+    fn into_mode<N>(self) -> FileDialogBuilder<N> {
+        FileDialogBuilder {
+            init_com: self.init_com,
+            default_path: self.default_path,
+            path: self.path,
+            filetypes: self.filetypes,
+            filename: self.filename,
+            filename_template: self.filename_template,
+            filename_counter: self.filename_counter,
+            implicit_all_files: self.implicit_all_files,
+            select_item: self.select_item,
+            max_selection: self.max_selection,
+            default_extension: self.default_extension,
+            auto_default_extension: self.auto_default_extension,
+            banner_text: self.banner_text,
+            title: self.title,
+            ok_button_label: self.ok_button_label,
+            parent: self.parent,
+            invalid_parent: self.invalid_parent,
+            restrict_to: self.restrict_to,
+            select_filename_base: self.select_filename_base,
+            thread_com: self.thread_com,
+            places: self.places,
+            options: self.options,
+            enforce_filter_match: self.enforce_filter_match,
+            ensure_extension: self.ensure_extension,
+            create_folders: self.create_folders,
+            force_default_folder: self.force_default_folder,
+            configure: self.configure,
+            mode: PhantomData,
+        }
+    }
+}
+
+impl FileOpenDialogBuilder {
+    /// Convert this into a [`FileSaveDialogBuilder`], keeping the shared options.
+    ///
+    /// Open-only options, like [`Self::max_selection`], are dropped.
+    pub fn into_save(self) -> FileSaveDialogBuilder {
+        let mut builder = self.into_mode();
+        builder.max_selection = None;
+        builder
+    }
+
+    /// Set the maximum number of items that may be selected with [`Self::execute_multiple`].
+    pub fn max_selection(&mut self, max_selection: usize) -> &mut Self {
+        self.max_selection = Some(max_selection);
+        self
+    }
+
+    /// Execute a dialog that allows selecting multiple items.
+    ///
+    /// # Errors
+    /// Returns an error if more than [`Self::max_selection`] items were selected.
+    pub fn execute_multiple(&self) -> Result<Vec<PathBuf>, NfdError> {
+        let _com_guard = self.init_thread_com()?;
+        let dialog = self.build()?;
+        let options = dialog
+            .get_options()
+            .map_err(NfdError::context("GetOptions"))?;
+        dialog
+            .set_options(options | FOS_ALLOWMULTISELECT)
+            .map_err(NfdError::context("SetOptions"))?;
+
+        show(&dialog, self.parent)?;
+        let items = dialog
+            .get_results()
+            .map_err(NfdError::context("GetResults"))?;
+
+        let count = items.get_count().map_err(NfdError::context("GetCount"))? as usize;
+        if let Some(max) = self.max_selection {
+            if count > max {
+                return Err(NfdError::TooManyItems { count, max });
+            }
+        }
+
+        let mut paths = Vec::with_capacity(count);
+        for item in items.iter().map_err(NfdError::context("GetCount"))? {
+            let path = item
+                .map_err(NfdError::context("IShellItemArray::GetItemAt"))?
+                .get_display_name(DisplayNameType::FileSysPath)
+                .map_err(NfdError::context("IShellItem::GetDisplayName"))?;
+            paths.push(PathBuf::from(path.as_os_string()));
+        }
+        debug!("dialog returned {} items", paths.len());
+
+        Ok(paths)
+    }
+}
+
+impl FileSaveDialogBuilder {
+    /// Convert this into a [`FileOpenDialogBuilder`], keeping the shared options.
+    ///
+    /// Save-only options, like [`Self::default_extension`], are dropped.
+    pub fn into_open(self) -> FileOpenDialogBuilder {
+        let mut builder = self.into_mode();
+        builder.default_extension = None;
+        builder.auto_default_extension = false;
+        builder.create_folders = false;
+        builder.enforce_filter_match = false;
+        builder.ensure_extension = false;
+        builder
+    }
+
+    /// Keep the dialog open if the typed filename has an extension that does not match the selected file type,
+    /// like `notes.txt` while a "PNG" file type is selected.
+    ///
+    /// Filenames without an extension are allowed, since the default extension is appended to them.
+    /// Only `*.ext` patterns are checked; file types with other patterns, like `*.*`, allow every filename.
+    /// The dialog stays open without an explanation, so consider saying what is expected in [`Self::banner_text`].
+    pub fn enforce_filter_match(&mut self) -> &mut Self {
+        self.enforce_filter_match = true;
+        self
+    }
+
+    /// Make sure the returned path has the extension of the file type selected when the dialog closed.
+    ///
+    /// Unlike [`Self::default_extension`], this is done after the dialog closes, so the result does not depend on what Windows appended.
+    /// If the path has an extension allowed by any `*.ext` pattern of the file type, it is kept.
+    /// Otherwise, the extension of the first pattern is appended, so `scan` becomes `scan.jpg` for `*.jpg;*.jpeg`,
+    /// and `scan.txt` becomes `scan.txt.jpg`.
+    /// File types whose first pattern is not a plain `*.ext` pattern, like `*.*`, leave the path unchanged.
+    ///
+    /// This applies to the `execute` methods that return a file system path, but not to [`Self::execute_as`], [`Self::execute_into`], or [`Self::execute_parts`].
+    pub fn ensure_extension(&mut self) -> &mut Self {
+        self.ensure_extension = true;
+        self
+    }
+
+    /// Allow the user to save into folders that do not exist yet, like by typing `new folder\report.txt`.
+    ///
+    /// After the dialog closes, the `execute` methods create the missing folders with [`std::fs::create_dir_all`].
+    /// If that fails, they return [`NfdError::Io`] and the selected path is lost,
+    /// so callers may want to report the error and show the dialog again.
+    /// [`Self::build`] only configures the dialog; callers that show it themselves must create the folders.
+    pub fn create_folders(&mut self) -> &mut Self {
+        self.create_folders = true;
+        self
+    }
+
+    /// Set up a "Save a Copy" dialog.
+    ///
+    /// This sets the title to "Save a Copy" and the OK button label to "Save",
+    /// and keeps the copy out of the user's recent items, since it is not the document being worked on.
+    /// The labels are in English; call [`Self::title`] and [`Self::ok_button_label`] afterwards to localize them.
+    pub fn save_a_copy(&mut self) -> &mut Self {
+        self.title("Save a Copy")
+            .ok_button_label("Save")
+            .dont_add_to_recent()
+    }
+
+    /// Set the extension to append to filenames typed without one, like `txt`.
+    ///
+    /// This should not include the leading `.`.
+    pub fn default_extension(&mut self, default_extension: impl AsRef<OsStr>) -> &mut Self {
+        self.default_extension = Some(default_extension.as_ref().into());
+        self
+    }
+
+    /// Whether to update the default extension to the first pattern of the selected file type, like `txt` for `*.txt;*.lbl`.
+    ///
+    /// This is off by default.
+    /// File types without a plain `*.ext` first pattern, like `*.*`, leave the default extension unchanged.
+    /// If [`Self::default_extension`] is not set, the first file type's extension is used initially.
+    pub fn auto_default_extension(&mut self, auto_default_extension: bool) -> &mut Self {
+        self.auto_default_extension = auto_default_extension;
+        self
+    }
+
+    /// Set the filename without an extension and the extension Windows should append to it, like `report` and `txt`.
+    ///
+    /// This avoids putting the extension in the filename, where it would be duplicated or fight the default extension logic.
+    /// If file types are set, Windows appends the extension of the selected file type instead when it has one,
+    /// so the default extension is only used for file types like `*.*`.
+    pub fn save_file_name(&mut self, base: impl AsRef<OsStr>, ext: impl AsRef<OsStr>) -> &mut Self {
+        self.filename(base).default_extension(ext)
+    }
+
+    /// Execute a dialog, returning the folder and the filename of the selected path separately.
+    ///
+    /// The folder keeps its trailing separator, like `C:\Users\name\`.
+    ///
+    /// # Errors
+    /// Returns [`NfdError::MissingFilename`] if the selected path does not end in a filename.
+    pub fn execute_parts(&self) -> Result<(PathBuf, OsString), NfdError> {
+        let _com_guard = self.init_thread_com()?;
+        let dialog = self.build()?;
+        // The filename is checked while splitting the path.
+        let checks = ResultChecks {
+            require_filename: false,
+            ..self.result_checks()
+        };
+        let path = show_for_display_name(&dialog, self.parent, checks)?;
+
+        split_filename(path.as_c_wide_str())
+    }
+}
+
+impl<M> Default for FileDialogBuilder<M>
+where
+    M: DialogMode,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A closure passed to [`FileDialogBuilder::configure`]
+pub type ConfigureFn = dyn Fn(&FileDialog) -> Result<(), HResult>;
+
+/// The result of [`FileDialogBuilder::execute_detailed`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogResult {
+    /// The selected path
+    pub path: PathBuf,
+
+    /// Whether the selected path already existed when the dialog closed.
+    ///
+    /// This is checked with the file system, so it is almost always true for open dialogs.
+    /// For save dialogs, this is true if the file will be overwritten.
+    pub exists: bool,
+}
+
+/// The configuration a [`FileDialogBuilder`] applies to a dialog, from [`FileDialogBuilder::options_snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogConfig {
+    /// The folder used if there is no recently used folder
+    pub default_path: Option<PathBuf>,
+
+    /// The folder the dialog opens in, regardless of past choices
+    pub folder: Option<PathBuf>,
+
+    /// A folder the user may not navigate outside of
+    pub restrict_to: Option<PathBuf>,
+
+    /// The names and specs of the file types
+    pub filetypes: Vec<(String, String)>,
+
+    /// The pre-filled filename
+    pub filename: Option<OsString>,
+
+    /// The extension appended to filenames typed without one
+    pub default_extension: Option<OsString>,
+
+    /// Whether the default extension follows the selected file type
+    pub auto_default_extension: bool,
+
+    /// The banner text
+    pub banner_text: Option<OsString>,
+
+    /// The title of the dialog window
+    pub title: Option<OsString>,
+
+    /// The label of the OK button
+    pub ok_button_label: Option<OsString>,
+
+    /// Known folders added to the places in the navigation pane, and whether they are added at the top
+    pub places: Vec<(KnownFolder, bool)>,
+
+    /// Option flags set in addition to the defaults
+    pub added_options: FILEOPENDIALOGOPTIONS,
+
+    /// Option flags cleared from the defaults
+    pub removed_options: FILEOPENDIALOGOPTIONS,
+}
+
+/// The result of [`FileDialogBuilder::execute_optional_detailed`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogOutcome {
+    /// The selected path, or `None` if the dialog was cancelled
+    pub path: Option<PathBuf>,
+
+    /// The 1-based index of the file type that was selected when the dialog closed.
+    ///
+    /// This is valid even if the dialog was cancelled, so it can be used to remember the user's preferred file type.
+    /// It may be 0 if no file types were set.
+    pub filetype_index: u32,
+}
+
+/// Convert an error from creating a dialog, calling out the common mistake of not initializing COM.
+fn create_dialog_error(hr: HResult) -> NfdError {
+    if hr.0 == CO_E_NOTINITIALIZED {
+        debug!("CoCreateInstance failed: {}", hr);
+        return NfdError::ComNotInitialized(hr);
+    }
+
+    NfdError::context("CoCreateInstance")(hr)
+}
+
+/// Show a dialog.
+fn show(dialog: &FileDialog, parent: Option<HWND>) -> Result<(), NfdError> {
+    debug!("showing dialog");
+    if let Err(e) = dialog.show(parent) {
+        debug!("show failed: {}", e);
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// What to check or do with the path of a dialog result
+#[derive(Default, Clone, Copy)]
+struct ResultChecks<'a> {
+    /// Whether the path must end in a filename
+    require_filename: bool,
+
+    /// Whether to create the parent folder of the path
+    create_folders: bool,
+
+    /// The file types to append the extension of the selected one from, if the path does not have it
+    ensure_extension: Option<&'a FileFilters<'static>>,
+}
+
+/// Show a dialog and get the file system path of its result.
+fn show_for_path(
+    dialog: &FileDialog,
+    parent: Option<HWND>,
+    checks: ResultChecks<'_>,
+) -> Result<PathBuf, NfdError> {
+    show(dialog, parent)?;
+    result_file_path(dialog, checks)
+}
+
+/// Show a dialog and get the file system path of its result as a wide string.
+fn show_for_display_name(
+    dialog: &FileDialog,
+    parent: Option<HWND>,
+    checks: ResultChecks<'_>,
+) -> Result<CoTaskMemWideString, NfdError> {
+    show(dialog, parent)?;
+    result_path(dialog, DisplayNameType::FileSysPath, checks)
+}
+
+/// Get the file system path of the result of a dialog that was shown.
+fn result_file_path(dialog: &FileDialog, checks: ResultChecks<'_>) -> Result<PathBuf, NfdError> {
+    let mut path =
+        PathBuf::from(result_path(dialog, DisplayNameType::FileSysPath, checks)?.as_os_string());
+
+    if let Some(filetypes) = checks.ensure_extension {
+        let index = dialog
+            .get_filetype_index()
+            .map_err(NfdError::context("GetFileTypeIndex"))?;
+        let spec = (index as usize)
+            .checked_sub(1)
+            .and_then(|index| filetypes.specs().nth(index));
+        if let Some(spec) = spec {
+            path = path_with_spec_extension(path, spec);
+        }
+    }
+    debug!("dialog returned '{}'", path.display());
+
+    Ok(path)
+}
+
+/// Append the extension of the first pattern of a filter spec to a path, unless it already has an extension the spec allows.
+///
+/// Paths are left unchanged if the first pattern is not a plain `*.ext` pattern, like `*.*`.
+fn path_with_spec_extension(path: PathBuf, spec: &CWideStr) -> PathBuf {
+    let extension = match spec_default_extension(spec) {
+        Some(extension) => extension,
+        None => return path,
+    };
+
+    let has_extension = path.extension().is_some()
+        && path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .map_or(false, |filename| {
+                filename_matches_spec(filename, &spec.to_string_lossy())
+            });
+    if has_extension {
+        return path;
+    }
+
+    let mut path = path.into_os_string();
+    path.push(".");
+    path.push(OsString::from_wide(extension.as_slice()));
+
+    PathBuf::from(path)
+}
+
+/// Get the path of the result of a dialog that was shown, in the given form.
+fn result_path(
+    dialog: &FileDialog,
+    display_type: DisplayNameType,
+    checks: ResultChecks<'_>,
+) -> Result<CoTaskMemWideString, NfdError> {
+    let shellitem = dialog.get_result()?;
+    let path = shellitem.get_display_name(display_type)?;
+
+    if checks.require_filename {
+        split_filename(path.as_c_wide_str())?;
+    }
+
+    if checks.create_folders {
+        let path = PathBuf::from(path.as_os_string());
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder)?;
+            debug!("created folder '{}'", folder.display());
+        }
+    }
+
+    Ok(path)
+}
+
+/// Split a path into its parent folder and filename, making sure that it ends in a filename.
+fn split_filename(path: &CWideStr) -> Result<(PathBuf, OsString), NfdError> {
+    let (full_path, filename_offset) =
+        get_full_path_name(path).map_err(NfdError::context("GetFullPathNameW"))?;
+
+    let filename_offset = match filename_offset {
+        Some(filename_offset) => filename_offset,
+        None => {
+            let path = PathBuf::from(OsString::from_wide(path.as_slice()));
+            debug!("'{}' has no filename", path.display());
+            return Err(NfdError::MissingFilename(path));
+        }
+    };
+
+    let (folder, filename) = full_path.as_slice().split_at(filename_offset);
+    Ok((
+        PathBuf::from(OsString::from_wide(folder)),
+        OsString::from_wide(filename),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use winapi::shared::winerror::E_FAIL;
+
+    /// A backend that records the calls made to it.
+    #[derive(Default)]
+    struct MockBackend {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl MockBackend {
+        fn record(&self, call: String) -> Result<(), NfdError> {
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+    }
+
+    impl FileDialogBackend for MockBackend {
+        fn set_default_folder(&self, path: &Path) -> Result<(), NfdError> {
+            self.record(format!("set_default_folder {}", path.display()))
+        }
+
+        fn set_folder(&self, path: &Path) -> Result<(), NfdError> {
+            self.record(format!("set_folder {}", path.display()))
+        }
+
+        fn set_filetypes(&self, filetypes: &FileFilters) -> Result<(), NfdError> {
+            self.record(format!("set_filetypes {}", filetypes.len()))
+        }
+
+        fn set_filename(&self, filename: &CWideStr) -> Result<(), NfdError> {
+            self.record(format!(
+                "set_filename {}",
+                String::from_utf16_lossy(filename.as_slice())
+            ))
+        }
+
+        fn set_default_extension(&self, extension: &CWideStr) -> Result<(), NfdError> {
+            self.record(format!(
+                "set_default_extension {}",
+                String::from_utf16_lossy(extension.as_slice())
+            ))
+        }
+
+        fn set_banner_text(&self, text: &CWideStr) -> Result<(), NfdError> {
+            self.record(format!(
+                "set_banner_text {}",
+                String::from_utf16_lossy(text.as_slice())
+            ))
+        }
+
+        fn set_title(&self, title: &CWideStr) -> Result<(), NfdError> {
+            self.record(format!(
+                "set_title {}",
+                String::from_utf16_lossy(title.as_slice())
+            ))
+        }
+
+        fn set_ok_button_label(&self, label: &CWideStr) -> Result<(), NfdError> {
+            self.record(format!(
+                "set_ok_button_label {}",
+                String::from_utf16_lossy(label.as_slice())
+            ))
+        }
+
+        fn set_auto_default_extension(
+            &self,
+            extensions: Vec<Option<CWideString>>,
+        ) -> Result<(), NfdError> {
+            let extensions: Vec<_> = extensions
+                .iter()
+                .map(|extension| match extension {
+                    Some(extension) => String::from_utf16_lossy(extension.as_slice()),
+                    None => "-".into(),
+                })
+                .collect();
+            self.record(format!(
+                "set_auto_default_extension {}",
+                extensions.join(",")
+            ))
+        }
+
+        fn restrict_to(&self, path: &Path) -> Result<(), NfdError> {
+            self.record(format!("restrict_to {}", path.display()))
+        }
+
+        fn select_filename_prefix(&self, len: usize) -> Result<(), NfdError> {
+            self.record(format!("select_filename_prefix {}", len))
+        }
+
+        fn add_place_known(&self, folder: KnownFolder, top: bool) -> Result<(), NfdError> {
+            self.record(format!("add_place_known {:?} {}", folder, top))
+        }
+
+        fn add_options(&self, options: FILEOPENDIALOGOPTIONS) -> Result<(), NfdError> {
+            self.record(format!("add_options 0x{:08X}", options))
+        }
+
+        fn remove_options(&self, options: FILEOPENDIALOGOPTIONS) -> Result<(), NfdError> {
+            self.record(format!("remove_options 0x{:08X}", options))
+        }
+
+        fn enforce_filter_match(&self, specs: Vec<CWideString>) -> Result<(), NfdError> {
+            let specs: Vec<_> = specs.iter().map(|spec| spec.to_string_lossy()).collect();
+            self.record(format!("enforce_filter_match {}", specs.join(",")))
+        }
+    }
+
+    #[test]
+    fn apply_records_calls() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder
+            .default_path("default".as_ref())
+            .path("folder".as_ref())
+            .filetype("toml", "*.toml")
+            .filename("Cargo.toml")
+            .default_extension("toml")
+            .banner_text("Pick a manifest");
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+
+        assert_eq!(
+            backend.calls.into_inner(),
+            [
+                "set_default_folder default",
+                "set_folder folder",
+                "set_filetypes 1",
+                "set_filename Cargo.toml",
+                "set_default_extension toml",
+                "set_banner_text Pick a manifest",
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_implicit_all_files() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.implicit_all_files(true);
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(backend.calls.into_inner(), ["set_filetypes 1"]);
+
+        let builder = FileOpenDialogBuilder::new();
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert!(backend.calls.into_inner().is_empty());
+    }
+
+    #[test]
+    fn apply_select_item() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.select_item("Cargo.toml".as_ref());
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            ["set_folder .", "set_filename Cargo.toml"]
+        );
+    }
+
+    #[test]
+    fn filetype_raw() {
+        let wide = |s: &str| s.encode_utf16().collect::<Vec<u16>>();
+
+        let mut builder = FileOpenDialogBuilder::new();
+        builder
+            .filetype_raw(wide("Text"), wide("*.txt"))
+            .expect("failed to add file type");
+        assert_eq!(builder.filetypes.len(), 1);
+
+        let error = builder
+            .filetype_raw(wide("Text"), wide("*.txt\0"))
+            .unwrap_err();
+        assert_eq!(error.nul_position(), 5);
+        assert_eq!(builder.filetypes.len(), 1);
+    }
+
+    #[test]
+    fn save_file_name() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder.save_file_name("report", "txt");
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            ["set_filename report", "set_default_extension txt"]
+        );
+    }
+
+    #[test]
+    fn auto_default_extension() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder
+            .filetype("Text", "*.txt;*.lbl")
+            .filetype("All Files", "*.*")
+            .auto_default_extension(true);
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            [
+                "set_filetypes 2",
+                "set_default_extension txt",
+                "set_auto_default_extension txt,-",
+            ]
+        );
+    }
+
+    #[test]
+    fn spec_default_extension() {
+        let extension = |spec: &str| {
+            let spec = CWideString::new(spec).expect("invalid c wide string");
+            super::spec_default_extension(&spec)
+                .map(|extension| String::from_utf16_lossy(extension.as_slice()))
+        };
+
+        assert_eq!(extension("*.txt").as_deref(), Some("txt"));
+        assert_eq!(extension(" *.txt ;*.lbl").as_deref(), Some("txt"));
+        assert_eq!(extension("*.tar.gz").as_deref(), Some("tar.gz"));
+        assert_eq!(extension("*.*"), None);
+        assert_eq!(extension("*."), None);
+        assert_eq!(extension("data*.bin"), None);
+        assert_eq!(extension(""), None);
+    }
+
+    #[test]
+    fn string_setters_accept_str_and_os_str() {
+        let name = OsString::from("Text");
+        let filename = String::from("notes");
+
+        let mut builder = FileSaveDialogBuilder::new();
+        builder
+            .filetype(&name, OsStr::new("*.txt"))
+            .filetype("All Files", String::from("*.*"))
+            .filename(&filename)
+            .default_extension("txt")
+            .banner_text(OsString::from("Pick a file"));
+        assert_eq!(builder.filetypes.len(), 2);
+        assert_eq!(builder.filename.as_deref(), Some(OsStr::new("notes")));
+        assert_eq!(
+            builder.default_extension.as_deref(),
+            Some(OsStr::new("txt"))
+        );
+
+        builder.filename(OsStr::new("report"));
+        assert_eq!(builder.filename.as_deref(), Some(OsStr::new("report")));
+
+        builder.save_file_name("summary", String::from("md"));
+        assert_eq!(builder.filename.as_deref(), Some(OsStr::new("summary")));
+        assert_eq!(builder.default_extension.as_deref(), Some(OsStr::new("md")));
+    }
+
+    #[test]
+    fn restrict_to() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.restrict_to("workspace".as_ref());
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            ["restrict_to workspace", "set_folder workspace"]
+        );
+
+        builder.path("workspace/src".as_ref());
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            ["restrict_to workspace", "set_folder workspace/src"]
+        );
+    }
+
+    #[test]
+    fn select_filename_base() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder.filename("report.tar.gz").select_filename_base();
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            ["set_filename report.tar.gz", "select_filename_prefix 10"]
+        );
+
+        builder.select_item("docs/notes".as_ref());
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            [
+                "set_filename report.tar.gz",
+                "set_folder docs",
+                "set_filename notes",
+                "select_filename_prefix 5"
+            ]
+        );
+    }
+
+    #[test]
+    fn add_place_known() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder
+            .add_place_known(KnownFolder::Downloads, true)
+            .add_place_known(KnownFolder::Documents, false);
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            [
+                "add_place_known Downloads true",
+                "add_place_known Documents false"
+            ]
+        );
+    }
+
+    #[test]
+    fn with_thread_com() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.with_thread_com(ComInit::MultiThreaded);
+        let builder = builder.into_save();
+        assert_eq!(builder.thread_com, Some(ComInit::MultiThreaded));
+
+        std::thread::spawn(|| {
+            let mut builder = FileOpenDialogBuilder::new();
+            assert!(builder
+                .init_thread_com()
+                .expect("failed to init com")
+                .is_none());
+
+            builder.with_thread_com(ComInit::ApartmentThreaded);
+            let guard = builder.init_thread_com().expect("failed to init com");
+            assert!(guard.is_some());
+        })
+        .join()
+        .expect("thread panicked");
+    }
+
+    #[test]
+    fn force_filesystem() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.force_filesystem().force_filesystem();
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            [format!("add_options 0x{:08X}", FOS_FORCEFILESYSTEM)]
+        );
+    }
+
+    #[test]
+    fn create_dialog_error_not_initialized() {
+        let error = create_dialog_error(HResult::from(CO_E_NOTINITIALIZED));
+        assert!(matches!(error, NfdError::ComNotInitialized(hr) if hr.0 == CO_E_NOTINITIALIZED));
+
+        let error = create_dialog_error(HResult::from(E_FAIL));
+        assert!(matches!(
+            error,
+            NfdError::HResultContext {
+                op: "CoCreateInstance",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn split_filename_requires_filename() {
+        let path = CWideString::new("C:\\foo\\bar.txt").expect("invalid c wide string");
+        let (folder, filename) = split_filename(&path).expect("path has a filename");
+        assert_eq!(folder, Path::new("C:\\foo\\"));
+        assert_eq!(filename, "bar.txt");
+
+        let path = CWideString::new("C:\\foo\\").expect("invalid c wide string");
+        let error = split_filename(&path).unwrap_err();
+        assert!(matches!(error, NfdError::MissingFilename(path) if path == Path::new("C:\\foo\\")));
+    }
+
+    #[test]
+    fn option_toggles() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder
+            .no_readonly_return()
+            .no_test_file_create()
+            .dont_add_to_recent()
+            .show_hidden();
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            [format!(
+                "add_options 0x{:08X}",
+                FOS_NOREADONLYRETURN
+                    | FOS_NOTESTFILECREATE
+                    | FOS_DONTADDTORECENT
+                    | FOS_FORCESHOWHIDDEN
+            )]
+        );
+    }
+
+    #[test]
+    fn force_default_folder() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder
+            .default_path("default".as_ref())
+            .force_default_folder();
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            ["set_default_folder default", "set_folder default"]
+        );
+
+        builder.path("path".as_ref());
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            ["set_default_folder default", "set_folder path"]
+        );
+    }
+
+    #[test]
+    fn owned_paths() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder
+            .default_path_buf(Path::new("default").join("dir"))
+            .path_buf(PathBuf::from("path"));
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            [
+                format!("set_default_folder {}", Path::new("default/dir").display()),
+                "set_folder path".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn configure_runs_in_order() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut builder = FileOpenDialogBuilder::new();
+        {
+            let calls = calls.clone();
+            builder.configure(move |_| {
+                calls.borrow_mut().push(1);
+                Ok(())
+            });
+        }
+        {
+            let calls = calls.clone();
+            builder.configure(move |dialog| {
+                calls.borrow_mut().push(2);
+                dialog.get_options().map(|_| ())
+            });
+        }
+
+        let _dialog = builder.build().expect("failed to build dialog");
+        assert_eq!(*calls.borrow(), [1, 2]);
+
+        builder.configure(|_| Err(HResult::from(E_FAIL)));
+        assert!(matches!(
+            builder.build(),
+            Err(NfdError::Configure { index: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn execute_async_with_configure() {
+        use std::future::Future;
+        use std::sync::Arc;
+        use std::task::Context;
+        use std::task::Poll;
+
+        struct NoopWaker;
+
+        impl std::task::Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.configure(|_| Ok(()));
+
+        let waker = Arc::new(NoopWaker).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut dialog = Box::pin(builder.execute_async());
+        assert!(matches!(
+            dialog.as_mut().poll(&mut cx),
+            Poll::Ready(Err(NfdError::AsyncConfigure))
+        ));
+    }
+
+    #[test]
+    fn save_a_copy() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder.save_a_copy().title("Enregistrer une copie");
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            [
+                format!("add_options 0x{:08X}", FOS_DONTADDTORECENT),
+                "set_title Enregistrer une copie".to_string(),
+                "set_ok_button_label Save".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn create_folders() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder.create_folders();
+        assert!(builder.result_checks().create_folders);
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            [format!(
+                "remove_options 0x{:08X}",
+                FOS_PATHMUSTEXIST | FOS_CREATEPROMPT
+            )]
+        );
+
+        let builder = builder.into_open();
+        assert!(!builder.create_folders);
+    }
+
+    #[test]
+    fn options_snapshot() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder
+            .default_path("C:\\Users".as_ref())
+            .filename("ignored.txt")
+            .select_item("C:\\Data\\level.txt".as_ref())
+            .implicit_all_files(true)
+            .title("Pick a level")
+            .force_filesystem();
+
+        let config = builder.options_snapshot();
+        assert_eq!(config.default_path, Some(PathBuf::from("C:\\Users")));
+        assert_eq!(config.folder, Some(PathBuf::from("C:\\Data")));
+        assert_eq!(config.filename, Some(OsString::from("level.txt")));
+        assert_eq!(
+            config.filetypes,
+            [("All Files".to_string(), "*.*".to_string())]
+        );
+        assert_eq!(config.title, Some(OsString::from("Pick a level")));
+        assert_eq!(config.added_options, FOS_FORCEFILESYSTEM);
+        assert_eq!(config.removed_options, 0);
+
+        builder.filetype("toml", "*.toml");
+        let config = builder.options_snapshot();
+        assert_eq!(
+            config.filetypes,
+            [("toml".to_string(), "*.toml".to_string())]
+        );
+    }
+
+    #[test]
+    fn expand_filename_template() {
+        let now = SYSTEMTIME {
+            wYear: 2024,
+            wMonth: 1,
+            wDayOfWeek: 2,
+            wDay: 2,
+            wHour: 9,
+            wMinute: 5,
+            wSecond: 30,
+            wMilliseconds: 0,
+        };
+        let expand = |template| super::expand_filename_template(template, &now, 3);
+
+        assert_eq!(expand("export_{date}.csv"), "export_2024-01-02.csv");
+        assert_eq!(expand("{date}_{time}"), "2024-01-02_09-05-30");
+        assert_eq!(expand("page {counter}.txt"), "page 3.txt");
+        assert_eq!(expand("{unknown}_{date"), "{unknown}_{date");
+        assert_eq!(expand("plain.txt"), "plain.txt");
+    }
+
+    #[test]
+    fn filename_template_counter() {
+        let mut builder = FileSaveDialogBuilder::new();
+        assert_eq!(builder.next_template_filename(), None);
+
+        builder.filename_template("scan {counter}.png");
+        assert_eq!(
+            builder.next_template_filename().as_deref(),
+            Some("scan 1.png")
+        );
+        assert_eq!(
+            builder.next_template_filename().as_deref(),
+            Some("scan 2.png")
+        );
+    }
+
+    #[test]
+    fn filename_matches_spec() {
+        use super::filename_matches_spec;
+
+        assert!(filename_matches_spec("image.png", "*.png"));
+        assert!(filename_matches_spec("IMAGE.PNG", "*.png"));
+        assert!(filename_matches_spec("image.jpg", "*.png; *.jpg"));
+        assert!(filename_matches_spec("image", "*.png"));
+        assert!(filename_matches_spec("notes.txt", "*.*"));
+        assert!(!filename_matches_spec("notes.txt", "*.png"));
+        assert!(!filename_matches_spec("archive.tar.gz", "*.png;*.jpg"));
+        assert!(filename_matches_spec("archive.tar.gz", "*.tar.gz"));
+        assert!(filename_matches_spec("ARCHIVE.TAR.GZ", "*.zip;*.tar.gz"));
+        assert!(!filename_matches_spec("archive.gz", "*.tar.gz"));
+    }
+
+    #[test]
+    fn apply_filters_from_pairs() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.filetypes =
+            FileFilters::from_pairs(&[("Text Files", "*.txt"), ("All Files", "*.*")])
+                .expect("failed to make filters");
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(backend.calls.into_inner(), ["set_filetypes 2"]);
+    }
+
+    #[test]
+    fn enforce_filter_match() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder
+            .filetype("PNG", "*.png")
+            .filetype("JPEG", "*.jpg;*.jpeg")
+            .enforce_filter_match();
+
+        let backend = MockBackend::default();
+        builder.apply(&backend).expect("failed to apply builder");
+        assert_eq!(
+            backend.calls.into_inner(),
+            ["set_filetypes 2", "enforce_filter_match *.png,*.jpg;*.jpeg"]
+        );
+
+        let builder = builder.into_open();
+        assert!(!builder.enforce_filter_match);
+    }
+
+    #[test]
+    fn into_hwnd() {
+        let window = 0x1234 as HWND;
+        assert_eq!(window.into_hwnd().ok(), Some(Some(window)));
+        assert_eq!(0x1234_isize.into_hwnd().ok(), Some(Some(window)));
+        assert_eq!(Some(window).into_hwnd().ok(), Some(Some(window)));
+        assert_eq!(None::<HWND>.into_hwnd().ok(), Some(None));
+        assert_eq!((std::ptr::null_mut() as HWND).into_hwnd().ok(), Some(None));
+    }
+
+    #[test]
+    fn invalid_parent() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.parent(0x1234_isize);
+        assert!(matches!(builder.build(), Err(NfdError::InvalidParent)));
+
+        builder.parent(None::<HWND>);
+        assert!(builder.parent.is_none());
+    }
+
+    #[test]
+    fn path_with_spec_extension() {
+        let extend = |path: &str, spec: &str| {
+            let spec = CWideString::new(spec).expect("invalid c wide string");
+            super::path_with_spec_extension(PathBuf::from(path), &spec)
+        };
+
+        assert_eq!(extend("scan", "*.jpg;*.jpeg"), Path::new("scan.jpg"));
+        assert_eq!(extend("scan.jpeg", "*.jpg;*.jpeg"), Path::new("scan.jpeg"));
+        assert_eq!(extend("scan.JPG", "*.jpg;*.jpeg"), Path::new("scan.JPG"));
+        assert_eq!(extend("scan.txt", "*.jpg"), Path::new("scan.txt.jpg"));
+        assert_eq!(extend("scan.txt", "*.*"), Path::new("scan.txt"));
+        assert_eq!(extend("archive", "*.tar.gz"), Path::new("archive.tar.gz"));
+        assert_eq!(
+            extend("archive.tar.gz", "*.tar.gz"),
+            Path::new("archive.tar.gz")
+        );
+        assert_eq!(
+            extend("ARCHIVE.TAR.GZ", "*.tar.gz"),
+            Path::new("ARCHIVE.TAR.GZ")
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use super::show_for_path;
+use super::DialogMode;
+use super::FileDialogBuilder;
+use crate::init_com_scoped_with;
+use crate::winuser;
+use crate::ComInit;
+use crate::NfdError;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use winapi::shared::windef::HWND;
+
+/// A dialog shown on another thread, from [`FileDialogBuilder::execute_async`]
+///
+/// This is a future that resolves to the selected path.
+/// Dropping it before it resolves cancels the dialog.
+pub struct AsyncDialog {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl AsyncDialog {
+    /// Show a dialog from a builder on a new thread.
+    pub(super) fn spawn<M>(builder: FileDialogBuilder<M>) -> Self
+    where
+        M: DialogMode + 'static,
+    {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+
+        let builder = SendBuilder(builder);
+        let thread_shared = shared.clone();
+        std::thread::spawn(move || {
+            let SendBuilder(builder) = builder;
+            let result = run(&builder, &thread_shared);
+
+            let mut shared = lock(&thread_shared);
+            shared.finished = true;
+            // An item the user picked before the dialog was closed is still returned.
+            shared.result = Some(match result {
+                Err(_) if shared.cancelled => Err(NfdError::Cancelled),
+                result => result,
+            });
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        });
+
+        Self { shared }
+    }
+
+    /// Make an [`AsyncDialog`] that already resolved, without showing a dialog.
+    pub(super) fn ready(result: Result<PathBuf, NfdError>) -> Self {
+        let shared = Shared {
+            result: Some(result),
+            finished: true,
+            ..Shared::default()
+        };
+
+        Self {
+            shared: Arc::new(Mutex::new(shared)),
+        }
+    }
+
+    /// Cancel the dialog.
+    ///
+    /// See [`CancelHandle::cancel`].
+    pub fn cancel(&self) {
+        cancel(&self.shared);
+    }
+
+    /// Get a handle that can cancel the dialog from another thread or task, while this future is awaited.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Future for AsyncDialog {
+    type Output = Result<PathBuf, NfdError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = lock(&self.shared);
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for AsyncDialog {
+    fn drop(&mut self) {
+        cancel(&self.shared);
+    }
+}
+
+/// A handle that cancels an [`AsyncDialog`], from [`AsyncDialog::cancel_handle`]
+#[derive(Clone)]
+pub struct CancelHandle {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl CancelHandle {
+    /// Cancel the dialog, making its [`AsyncDialog`] resolve to [`NfdError::Cancelled`].
+    ///
+    /// This may be called from any thread.
+    /// Cancellation is best-effort:
+    /// `Close` is called on the dialog from the thread showing it once that thread dispatches the request,
+    /// which only happens while the dialog is shown.
+    /// If the dialog is not shown yet, it is not shown at all.
+    /// If it already closed, or the user picks an item before the request is dispatched,
+    /// this does nothing, and the future resolves to the picked item.
+    pub fn cancel(&self) {
+        cancel(&self.shared);
+    }
+}
+
+/// The state shared between an [`AsyncDialog`], its [`CancelHandle`]s, and the thread showing the dialog
+#[derive(Default)]
+struct Shared {
+    /// The result of the dialog, until it is returned by the future
+    result: Option<Result<PathBuf, NfdError>>,
+
+    /// Whether the thread showing the dialog finished
+    finished: bool,
+
+    /// Whether the dialog was cancelled
+    cancelled: bool,
+
+    /// The waker of the task awaiting the future
+    waker: Option<Waker>,
+
+    /// The window of the [`winuser::DialogCloser`] of the dialog, while it is shown
+    closer: Option<usize>,
+}
+
+/// A builder that can be sent to the thread that shows the dialog
+struct SendBuilder<M>(FileDialogBuilder<M>);
+
+// Safety:
+// Every field of the builder is `Send`, except for:
+// * `parent`, a window handle, which identifies the window from any thread.
+// * `filetypes`, whose `COMDLG_FILTERSPEC`s only point into its own storage.
+//   That storage is either owned, and moves to the other thread along with the pointers,
+//   or `'static` and never mutated, so no other thread can observe it changing.
+// * `configure`, whose `Rc`s can not be shared between threads.
+//   `execute_async` does not spawn a thread if there are any, so the vector is always empty here.
+// `M` is only a marker, and is never instantiated.
+unsafe impl<M> Send for SendBuilder<M> {}
+
+/// Lock the shared state.
+///
+/// Poisoning is ignored, since the state is never left half-updated.
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Show a dialog on the current thread, letting it be closed through the shared state.
+fn run<M>(builder: &FileDialogBuilder<M>, shared: &Mutex<Shared>) -> Result<PathBuf, NfdError>
+where
+    M: DialogMode,
+{
+    let _com_guard = init_com_scoped_with(ComInit::ApartmentThreaded)
+        .map_err(NfdError::context("CoInitializeEx"))?;
+    let dialog = builder.build()?;
+
+    // Safety:
+    // The closer is dropped before the dialog, and the dialog is not moved.
+    let closer = unsafe { winuser::DialogCloser::new(&dialog) }
+        .map_err(|e| NfdError::context("CreateWindowExW")(e.into()))?;
+    {
+        let mut shared = lock(shared);
+        if shared.cancelled {
+            debug!("dialog was cancelled before it was shown");
+            return Err(NfdError::Cancelled);
+        }
+        shared.closer = Some(closer.window() as usize);
+    }
+
+    let result = show_for_path(&dialog, builder.parent, builder.result_checks());
+
+    // The closer must not receive requests once it is destroyed.
+    lock(shared).closer = None;
+
+    result
+}
+
+/// Cancel a dialog through its shared state.
+fn cancel(shared: &Mutex<Shared>) {
+    let mut shared = lock(shared);
+    if shared.finished || shared.cancelled {
+        return;
+    }
+    shared.cancelled = true;
+    debug!("cancelling dialog");
+
+    // This is posted while the state is locked, so the closer can not be destroyed before the request is queued.
+    if let Some(closer) = shared.closer {
+        if !winuser::post_close_dialog(closer as HWND) {
+            debug!("failed to post close request");
+        }
+    }
+}
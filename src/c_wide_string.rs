@@ -1,3 +1,4 @@
+use skylight::CoTaskMemWideString;
 use std::ffi::OsStr;
 use std::fmt::Write;
 use std::ops::Deref;
@@ -94,6 +95,14 @@ impl CWideString {
         Ok(Self(data.into_boxed_slice()))
     }
 
+    /// Make a new [`CWideString`] by copying a slice that is nul terminated.
+    ///
+    /// # Errors
+    /// Errors if data contains interior nuls or is not nul terminated
+    pub fn from_slice_with_nul(data: &[u16]) -> Result<Self, FromVecWithNulError> {
+        Self::from_vec_with_nul(data.to_vec())
+    }
+
     /// Make a new [`CWideString`] from a vec that is nul terminated without checks.
     ///
     /// # Safety
@@ -117,6 +126,38 @@ impl Deref for CWideString {
     }
 }
 
+impl IntoIterator for CWideString {
+    type Item = Result<char, std::char::DecodeUtf16Error>;
+    type IntoIter = IntoChars;
+
+    /// Iterate over the chars in this string, consuming it.
+    ///
+    /// Use [`CWideStr::chars`] to iterate without consuming the string.
+    fn into_iter(self) -> Self::IntoIter {
+        let mut data = self.0.into_vec();
+        let nul = data.pop();
+        debug_assert_eq!(nul, Some(0), "missing NUL terminator");
+
+        IntoChars(std::char::decode_utf16(data.into_iter()))
+    }
+}
+
+/// An owning iterator over the chars in a [`CWideString`]
+#[derive(Debug, Clone)]
+pub struct IntoChars(std::char::DecodeUtf16<std::vec::IntoIter<u16>>);
+
+impl Iterator for IntoChars {
+    type Item = Result<char, std::char::DecodeUtf16Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
 impl std::fmt::Debug for CWideString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.as_c_wide_str().fmt(f)
@@ -129,6 +170,56 @@ impl std::borrow::Borrow<CWideStr> for CWideString {
     }
 }
 
+/// A builder for a [`CWideString`].
+///
+/// This appends to a single buffer, adding the NUL terminator once in [`CWideStringBuilder::finish`].
+#[derive(Debug, Default, Clone)]
+pub struct CWideStringBuilder {
+    data: Vec<u16>,
+}
+
+impl CWideStringBuilder {
+    /// Make a new, empty [`CWideStringBuilder`].
+    pub fn new() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    /// Make a new, empty [`CWideStringBuilder`] with room for `capacity` wide chars and the NUL terminator.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(capacity + 1),
+        }
+    }
+
+    /// Append a str.
+    pub fn push_str(&mut self, s: &str) {
+        self.data.extend(s.encode_utf16());
+    }
+
+    /// Append a single wide char.
+    pub fn push_unit(&mut self, unit: u16) {
+        self.data.push(unit);
+    }
+
+    /// Add the NUL terminator and make a [`CWideString`].
+    ///
+    /// # Errors
+    /// Errors if any interior NULs were pushed.
+    pub fn finish(self) -> Result<CWideString, NulError> {
+        CWideString::new(self.data)
+    }
+}
+
+/// Format into a [`CWideStringBuilder`] with `write!`.
+///
+/// Writing never fails; interior NULs are reported by [`CWideStringBuilder::finish`].
+impl Write for CWideStringBuilder {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct NulError(usize, Vec<u16>);
 
@@ -187,6 +278,9 @@ impl std::fmt::Display for FromVecWithNulError {
 
 impl std::error::Error for FromVecWithNulError {}
 
+/// A borrowed, NUL-terminated wide string.
+///
+/// The inner slice always ends with the NUL terminator, so it is never empty.
 pub struct CWideStr {
     inner: [u16],
 }
@@ -198,9 +292,24 @@ impl CWideStr {
     /// * data must be nul terminated
     /// * data must contain no interior nuls
     pub unsafe fn from_wide_with_nul_unchecked(data: &[u16]) -> &Self {
+        debug_assert_eq!(data.last(), Some(&0), "data is not nul terminated");
         &*(data as *const [u16] as *const CWideStr)
     }
 
+    /// Make a [`CWideStr`] from a pointer to a NUL-terminated wide string.
+    ///
+    /// # Safety
+    /// * ptr must be non-null and point to a NUL-terminated wide string
+    /// * the string must be valid and unchanged for the lifetime `'a`
+    pub unsafe fn from_ptr<'a>(ptr: *const u16) -> &'a Self {
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+
+        Self::from_wide_with_nul_unchecked(std::slice::from_raw_parts(ptr, len + 1))
+    }
+
     /// Get a pointer to the data.
     pub fn as_ptr(&self) -> *const u16 {
         self.inner.as_ptr()
@@ -210,6 +319,7 @@ impl CWideStr {
     ///
     /// Does NOT include the NUL terminator.
     pub fn as_slice(&self) -> &[u16] {
+        debug_assert!(!self.inner.is_empty(), "missing NUL terminator");
         &self.inner[..self.inner.len() - 1]
     }
 
@@ -217,13 +327,75 @@ impl CWideStr {
     ///
     /// Does include the NUL terminator.
     pub fn as_slice_with_nul(&self) -> &[u16] {
+        debug_assert!(!self.inner.is_empty(), "missing NUL terminator");
         &self.inner[..self.inner.len()]
     }
 
+    /// Check if this string contains the given string.
+    ///
+    /// An empty needle is always contained.
+    pub fn contains(&self, needle: &CWideStr) -> bool {
+        let needle = needle.as_slice();
+        if needle.is_empty() {
+            return true;
+        }
+
+        self.as_slice()
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    /// Check if this string contains the given wide char.
+    pub fn contains_unit(&self, unit: u16) -> bool {
+        self.as_slice().contains(&unit)
+    }
+
+    /// Split this string on a separator wide char.
+    ///
+    /// The segments are not NUL terminated, so they are returned as slices.
+    /// Like [`str::split`], leading, trailing, and consecutive separators yield empty segments,
+    /// and an empty string yields a single empty segment.
+    pub fn split(&self, sep: u16) -> impl Iterator<Item = &[u16]> + '_ {
+        self.as_slice().split(move |&unit| unit == sep)
+    }
+
+    /// Make a copy of this string with leading and trailing whitespace removed.
+    ///
+    /// A wide char counts as whitespace if it is a single-unit char for which [`char::is_whitespace`] is true,
+    /// like spaces, tabs, newlines, and the ideographic space.
+    /// Surrogates are never whitespace.
+    pub fn trim(&self) -> CWideString {
+        let is_whitespace = |unit: &u16| matches!(std::char::from_u32(u32::from(*unit)), Some(c) if c.is_whitespace());
+
+        let slice = self.as_slice();
+        let start = slice
+            .iter()
+            .position(|unit| !is_whitespace(unit))
+            .unwrap_or(slice.len());
+        let end = slice
+            .iter()
+            .rposition(|unit| !is_whitespace(unit))
+            .map_or(start, |index| index + 1);
+
+        CWideString::new(slice[start..end].to_vec()).expect("CWideStr contained an interior NUL")
+    }
+
     /// Try to iterate over the chars in this string.
     pub fn chars(&self) -> impl Iterator<Item = Result<char, std::char::DecodeUtf16Error>> + '_ {
         std::char::decode_utf16(self.as_slice().iter().copied())
     }
+
+    /// Convert this string into a [`String`], failing on invalid UTF-16 like unpaired surrogates.
+    ///
+    /// Use [`CWideStr::to_string_lossy`] to replace invalid UTF-16 instead.
+    pub fn to_string(&self) -> Result<String, std::char::DecodeUtf16Error> {
+        self.chars().collect()
+    }
+
+    /// Convert this string into a [`String`], replacing invalid UTF-16 with [`std::char::REPLACEMENT_CHARACTER`].
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_slice())
+    }
 }
 
 impl std::fmt::Debug for CWideStr {
@@ -244,6 +416,34 @@ impl std::fmt::Debug for CWideStr {
     }
 }
 
+impl PartialEq for CWideStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice_with_nul() == other.as_slice_with_nul()
+    }
+}
+
+impl Eq for CWideStr {}
+
+impl PartialOrd for CWideStr {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// This must match the derived impl for `CWideString`, which compares the units including the NUL terminator.
+impl Ord for CWideStr {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_slice_with_nul().cmp(other.as_slice_with_nul())
+    }
+}
+
+// This must match the derived impl for `CWideString`, which hashes the units including the NUL terminator.
+impl std::hash::Hash for CWideStr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_slice_with_nul().hash(state)
+    }
+}
+
 impl std::ops::Index<std::ops::RangeFrom<usize>> for CWideStr {
     type Output = CWideStr;
 
@@ -268,3 +468,232 @@ impl std::borrow::ToOwned for CWideStr {
         CWideString::new(self).expect("invalid CWideStr")
     }
 }
+
+/// An extension trait to view a [`CoTaskMemWideString`] as a [`CWideStr`].
+pub trait CoTaskMemWideStringExt {
+    /// View this as a [`CWideStr`].
+    fn as_c_wide_str(&self) -> &CWideStr;
+}
+
+impl CoTaskMemWideStringExt for CoTaskMemWideString {
+    fn as_c_wide_str(&self) -> &CWideStr {
+        let slice = self.as_slice();
+
+        // Safety:
+        // COM strings are NUL terminated, so the terminator directly follows the slice.
+        // The slice ends at the first NUL, so there are no interior NULs.
+        unsafe {
+            let slice = std::slice::from_raw_parts(slice.as_ptr(), slice.len() + 1);
+            CWideStr::from_wide_with_nul_unchecked(slice)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builder() {
+        let mut builder = CWideStringBuilder::with_capacity(11);
+        builder.push_str("*.txt");
+        builder.push_unit(u16::from(b';'));
+        builder.push_str("*.lbl");
+        let string = builder.finish().expect("failed to finish");
+        let expected = CWideString::new("*.txt;*.lbl").expect("invalid c wide string");
+        assert_eq!(string, expected);
+
+        let mut builder = CWideStringBuilder::new();
+        builder.push_str("a");
+        builder.push_unit(0);
+        let error = builder.finish().unwrap_err();
+        assert_eq!(error.nul_position(), 1);
+    }
+
+    #[test]
+    fn builder_write() {
+        let mut builder = CWideStringBuilder::new();
+        write!(builder, "{} ({} items)", "Images", 3).expect("failed to write");
+        let string = builder.finish().expect("failed to finish");
+        let expected = CWideString::new("Images (3 items)").expect("invalid c wide string");
+        assert_eq!(string, expected);
+
+        let mut builder = CWideStringBuilder::new();
+        write!(builder, "a{}b", '\0').expect("failed to write");
+        let error = builder.finish().unwrap_err();
+        assert_eq!(error.nul_position(), 1);
+    }
+
+    #[test]
+    fn from_ptr() {
+        let string = CWideString::new("abc").expect("invalid c wide string");
+        let borrowed = unsafe { CWideStr::from_ptr(string.as_ptr()) };
+        assert_eq!(borrowed, string.as_c_wide_str());
+    }
+
+    #[test]
+    fn contains() {
+        let haystack = CWideString::new("hello world").expect("invalid c wide string");
+        let needle = CWideString::new("o w").expect("invalid c wide string");
+        let missing = CWideString::new("low").expect("invalid c wide string");
+        let empty = CWideString::new("").expect("invalid c wide string");
+
+        assert!(haystack.contains(&needle));
+        assert!(!haystack.contains(&missing));
+        assert!(haystack.contains(&empty));
+        assert!(empty.contains(&empty));
+        assert!(!empty.contains(&needle));
+        assert!(!needle.contains(&haystack));
+
+        assert!(haystack.contains_unit(u16::from(b' ')));
+        assert!(!haystack.contains_unit(u16::from(b'z')));
+        assert!(!haystack.contains_unit(0));
+    }
+
+    #[test]
+    fn split() {
+        let sep = u16::from(b';');
+        let split = |s: &str| -> Vec<String> {
+            CWideString::new(s)
+                .expect("invalid c wide string")
+                .split(sep)
+                .map(String::from_utf16_lossy)
+                .collect()
+        };
+
+        assert_eq!(split("*.txt;*.lbl"), ["*.txt", "*.lbl"]);
+        assert_eq!(split(";*.txt"), ["", "*.txt"]);
+        assert_eq!(split("*.txt;"), ["*.txt", ""]);
+        assert_eq!(split("*.txt;;*.lbl"), ["*.txt", "", "*.lbl"]);
+        assert_eq!(split("*.txt"), ["*.txt"]);
+        assert_eq!(split(""), [""]);
+    }
+
+    #[test]
+    fn trim() {
+        let trim = |s: &str| {
+            let trimmed = CWideString::new(s).expect("invalid c wide string").trim();
+            String::from_utf16_lossy(trimmed.as_slice())
+        };
+
+        assert_eq!(trim("  name.txt  "), "name.txt");
+        assert_eq!(trim("\tname.txt\t"), "name.txt");
+        assert_eq!(trim(" \t\r\n name .txt \u{3000}"), "name .txt");
+        assert_eq!(trim("name.txt"), "name.txt");
+        assert_eq!(trim(" \t "), "");
+        assert_eq!(trim(""), "");
+    }
+
+    #[test]
+    fn from_slice_with_nul() {
+        let mut buffer = [0_u16; 8];
+        for (unit, c) in buffer.iter_mut().zip("abc".encode_utf16()) {
+            *unit = c;
+        }
+
+        let string = CWideString::from_slice_with_nul(&buffer[..4]).expect("invalid slice");
+        assert_eq!(
+            string,
+            CWideString::new("abc").expect("invalid c wide string")
+        );
+
+        assert!(CWideString::from_slice_with_nul(&buffer[..3]).is_err());
+        assert!(CWideString::from_slice_with_nul(&buffer[..5]).is_err());
+        assert!(CWideString::from_slice_with_nul(&[]).is_err());
+    }
+
+    #[test]
+    fn hash_map_lookup_by_c_wide_str() {
+        let mut map = std::collections::HashMap::new();
+        map.insert(CWideString::new("abc").expect("invalid c wide string"), 1);
+        map.insert(CWideString::new("abcd").expect("invalid c wide string"), 2);
+
+        let key = CWideString::new("abc").expect("invalid c wide string");
+        let key: &CWideStr = &key;
+        assert_eq!(map.get(key), Some(&1));
+        assert_eq!(map.get(&key[1..]), None);
+    }
+
+    #[test]
+    fn ord_matches_owned() {
+        let strings: Vec<CWideString> = ["b", "abc", "", "ab", "a\u{e9}", "B"]
+            .iter()
+            .map(|s| CWideString::new(*s).expect("invalid c wide string"))
+            .collect();
+
+        for a in strings.iter() {
+            for b in strings.iter() {
+                let borrowed_a: &CWideStr = a;
+                let borrowed_b: &CWideStr = b;
+                assert_eq!(a.cmp(b), borrowed_a.cmp(borrowed_b));
+            }
+        }
+
+        let mut owned = strings.clone();
+        owned.sort();
+        let mut borrowed: Vec<&CWideStr> = strings.iter().map(|s| s.as_c_wide_str()).collect();
+        borrowed.sort();
+        assert!(owned
+            .iter()
+            .map(|s| s.as_c_wide_str())
+            .eq(borrowed.into_iter()));
+    }
+
+    #[test]
+    fn index_range_from() {
+        let string = CWideString::new("abc").expect("invalid c wide string");
+        let len = string.as_slice().len();
+
+        assert_eq!(&string[0..], string.as_c_wide_str());
+        assert_eq!(string[len - 1..].as_slice(), &[u16::from(b'c')]);
+        assert_eq!(string[len - 1..].as_slice_with_nul(), &[u16::from(b'c'), 0]);
+
+        // Indexing at the NUL terminator yields an empty string.
+        assert!(string[len..].as_slice().is_empty());
+        assert_eq!(string[len..].as_slice_with_nul(), &[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn index_range_from_out_of_bounds() {
+        let string = CWideString::new("abc").expect("invalid c wide string");
+        let _ = &string[4..];
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not nul terminated")]
+    fn from_wide_with_nul_unchecked_empty() {
+        let _ = unsafe { CWideStr::from_wide_with_nul_unchecked(&[]) };
+    }
+
+    #[test]
+    fn to_string() {
+        let valid = CWideString::new("level.txt").expect("invalid c wide string");
+        assert_eq!(valid.to_string().as_deref(), Ok("level.txt"));
+        assert_eq!(valid.to_string_lossy(), "level.txt");
+
+        let invalid = CWideString::new(vec![u16::from(b'a'), 0xD800, u16::from(b'b')])
+            .expect("invalid c wide string");
+        let error = invalid.to_string().unwrap_err();
+        assert_eq!(error.unpaired_surrogate(), 0xD800);
+        assert_eq!(invalid.to_string_lossy(), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn into_iter() {
+        let string = CWideString::new("abc").expect("invalid c wide string");
+        let chars: Result<String, _> = string.into_iter().collect();
+        assert_eq!(chars.as_deref(), Ok("abc"));
+
+        let string =
+            CWideString::new(vec![u16::from(b'a'), 0xDC00]).expect("invalid c wide string");
+        let mut chars = string.into_iter();
+        assert_eq!(chars.next(), Some(Ok('a')));
+        assert_eq!(
+            chars.next().map(|c| c.map_err(|e| e.unpaired_surrogate())),
+            Some(Err(0xDC00))
+        );
+        assert_eq!(chars.next(), None);
+    }
+}
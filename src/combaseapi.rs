@@ -0,0 +1,90 @@
+use skylight::HResult;
+use std::marker::PhantomData;
+use winapi::shared::winerror::FAILED;
+use winapi::um::combaseapi::CoInitializeEx;
+use winapi::um::combaseapi::CoUninitialize;
+use winapi::um::objbase::COINIT_APARTMENTTHREADED;
+use winapi::um::objbase::COINIT_MULTITHREADED;
+
+/// A guard that uninitializes COM on the current thread when dropped.
+///
+/// COM is initialized per-thread, so this is neither `Send` nor `Sync`.
+#[derive(Debug)]
+pub struct ComGuard {
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        unsafe {
+            CoUninitialize();
+        }
+    }
+}
+
+/// The concurrency model to init COM with
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ComInit {
+    /// A multithreaded apartment
+    MultiThreaded,
+
+    /// A single-threaded apartment.
+    ///
+    /// Threads that own windows should use this.
+    ApartmentThreaded,
+}
+
+/// Init a multithreaded COM runtime on the current thread, returning a guard that uninitializes it on drop.
+///
+/// Every successful call to `CoInitializeEx` must be balanced by a call to `CoUninitialize`,
+/// including calls that report that COM was already initialized on this thread.
+/// If COM was already initialized with a different concurrency model, an error is returned and nothing will be uninitialized.
+pub fn init_com_scoped() -> Result<ComGuard, HResult> {
+    init_com_scoped_with(ComInit::MultiThreaded)
+}
+
+/// Init a COM runtime with the given concurrency model on the current thread, returning a guard that uninitializes it on drop.
+///
+/// See [`init_com_scoped`].
+pub fn init_com_scoped_with(mode: ComInit) -> Result<ComGuard, HResult> {
+    let coinit = match mode {
+        ComInit::MultiThreaded => COINIT_MULTITHREADED,
+        ComInit::ApartmentThreaded => COINIT_APARTMENTTHREADED,
+    };
+    let ret = unsafe { CoInitializeEx(std::ptr::null_mut(), coinit) };
+
+    if FAILED(ret) {
+        let error = HResult::from(ret);
+        debug!("CoInitializeEx failed: {}", error);
+        return Err(error);
+    }
+    debug!("initialized scoped com");
+
+    Ok(ComGuard {
+        _not_send: PhantomData,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nested_com_guards() {
+        let outer = init_com_scoped().expect("failed to init com");
+        let inner = init_com_scoped().expect("failed to init com again");
+        drop(inner);
+        drop(outer);
+    }
+
+    #[test]
+    fn mismatched_com_guard() {
+        std::thread::spawn(|| {
+            let _guard =
+                init_com_scoped_with(ComInit::ApartmentThreaded).expect("failed to init com");
+            init_com_scoped_with(ComInit::MultiThreaded).unwrap_err();
+        })
+        .join()
+        .expect("thread panicked");
+    }
+}
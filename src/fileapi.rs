@@ -3,20 +3,33 @@ use crate::c_wide_string::CWideString;
 use skylight::HResult;
 use std::convert::TryInto;
 use winapi::shared::minwindef::MAX_PATH;
+use winapi::shared::winerror::E_INVALIDARG;
+use winapi::um::fileapi::GetFileType;
+use winapi::um::fileapi::GetFinalPathNameByHandleW;
 use winapi::um::fileapi::GetFullPathNameW;
+use winapi::um::fileapi::GetLogicalDriveStringsW;
+use winapi::um::winbase::FILE_NAME_NORMALIZED;
+use winapi::um::winbase::FILE_TYPE_DISK;
+use winapi::um::winbase::VOLUME_NAME_DOS;
+use winapi::um::winnt::HANDLE;
 
 /// Get the full path name.
 ///
 /// Returns a tuple. If the path refers to a file, the second element of the tuple is the starting index of the filename.
 /// Get the [`CWideString`] as a slice and index that to access the filename.
 pub fn get_full_path_name(input_path: &CWideStr) -> Result<(CWideString, Option<usize>), HResult> {
-    let mut path = Vec::with_capacity(MAX_PATH);
+    let mut path: Vec<u16> = Vec::with_capacity(MAX_PATH);
     let mut file_part = std::ptr::null_mut();
 
-    let mut size = MAX_PATH as u32;
     loop {
-        size = unsafe {
-            GetFullPathNameW(input_path.as_ptr(), size, path.as_mut_ptr(), &mut file_part)
+        let capacity: u32 = path.capacity().try_into().unwrap_or(u32::MAX);
+        let size = unsafe {
+            GetFullPathNameW(
+                input_path.as_ptr(),
+                capacity,
+                path.as_mut_ptr(),
+                &mut file_part,
+            )
         };
 
         if size == 0 {
@@ -24,18 +37,14 @@ pub fn get_full_path_name(input_path: &CWideStr) -> Result<(CWideString, Option<
         }
 
         let size_usize: usize = size.try_into().expect("path len cannot fit in a usize");
-        if size_usize < MAX_PATH {
+
+        // On success, the returned size does not include the NUL terminator, so it is smaller than the buffer.
+        // Otherwise, it is the required buffer size, including the NUL terminator.
+        if size < capacity {
             unsafe {
                 path.set_len(size_usize + 1);
             }
-            let filename_offset = if !file_part.is_null() {
-                // TODO: I think i'm doing this right, but is file_part always guaranteed to be larger than the path ptr?
-                let diff = file_part as usize - path.as_ptr() as usize;
-                // Divide by 2 since there are 2 bytes per wide char
-                Some(diff / 2)
-            } else {
-                None
-            };
+            let filename_offset = filename_offset(path.as_ptr(), file_part, size_usize);
             let ret = CWideString::from_vec_with_nul(path).expect("path contained interior NULs");
             return Ok((ret, filename_offset));
         }
@@ -44,3 +53,180 @@ pub fn get_full_path_name(input_path: &CWideStr) -> Result<(CWideString, Option<
         path.reserve(size_usize);
     }
 }
+
+/// Get the root paths of the logical drives, like `C:\`.
+pub fn get_logical_drive_strings() -> Result<Vec<CWideString>, HResult> {
+    let mut buffer: Vec<u16> = Vec::new();
+
+    loop {
+        let capacity: u32 = buffer.capacity().try_into().unwrap_or(u32::MAX);
+        let size = unsafe { GetLogicalDriveStringsW(capacity, buffer.as_mut_ptr()) };
+
+        if size == 0 {
+            return Err(HResult::get_last_error());
+        }
+
+        let size_usize: usize = size
+            .try_into()
+            .expect("drive strings len cannot fit in a usize");
+
+        // On success, the returned size does not include the final NUL terminator, so it is smaller than the buffer.
+        // Otherwise, it is the required buffer size.
+        if size < capacity {
+            unsafe {
+                buffer.set_len(size_usize);
+            }
+
+            // The buffer holds NUL-terminated strings back to back.
+            let drives = buffer
+                .split(|&unit| unit == 0)
+                .filter(|drive| !drive.is_empty())
+                .map(|drive| CWideString::new(drive.to_vec()).expect("drive contains NUL"))
+                .collect();
+            return Ok(drives);
+        }
+
+        // The buffer was too small. Resize and try again.
+        buffer.reserve(size_usize + 1);
+    }
+}
+
+/// Get the path of an open file handle, like `\\?\C:\foo\bar.txt`.
+///
+/// Only handles to files and folders on disk have a path.
+/// Other handles, like pipes, sockets, and consoles, return `E_INVALIDARG`.
+///
+/// # Safety
+/// `handle` must be a valid handle.
+pub unsafe fn get_final_path_name_by_handle(handle: HANDLE) -> Result<CWideString, HResult> {
+    if GetFileType(handle) != FILE_TYPE_DISK {
+        return Err(HResult::from(E_INVALIDARG));
+    }
+
+    let mut path: Vec<u16> = Vec::with_capacity(MAX_PATH);
+
+    loop {
+        let capacity: u32 = path.capacity().try_into().unwrap_or(u32::MAX);
+        let size = GetFinalPathNameByHandleW(
+            handle,
+            path.as_mut_ptr(),
+            capacity,
+            FILE_NAME_NORMALIZED | VOLUME_NAME_DOS,
+        );
+
+        if size == 0 {
+            return Err(HResult::get_last_error());
+        }
+
+        let size_usize: usize = size.try_into().expect("path len cannot fit in a usize");
+
+        // On success, the returned size does not include the NUL terminator, so it is smaller than the buffer.
+        // Otherwise, it is the required buffer size, including the NUL terminator.
+        if size < capacity {
+            path.set_len(size_usize + 1);
+            return Ok(CWideString::from_vec_with_nul(path).expect("path contained interior NULs"));
+        }
+
+        // The buffer was too small. Resize and try again.
+        path.reserve(size_usize);
+    }
+}
+
+/// Convert the file part pointer returned by `GetFullPathNameW` into an index into the path.
+///
+/// Returns `None` if there is no file part, or if it does not point to a wide char inside the path.
+fn filename_offset(path: *const u16, file_part: *const u16, len: usize) -> Option<usize> {
+    if file_part.is_null() {
+        return None;
+    }
+
+    let diff = (file_part as usize).checked_sub(path as usize)?;
+
+    // The pointers are both to wide chars in the same buffer, so the distance must be a whole number of wide chars.
+    let unit_size = std::mem::size_of::<u16>();
+    debug_assert_eq!(
+        diff % unit_size,
+        0,
+        "file part is not aligned to a wide char"
+    );
+    if diff % unit_size != 0 {
+        return None;
+    }
+
+    let offset = diff / unit_size;
+    if offset >= len {
+        return None;
+    }
+
+    Some(offset)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn full_path_name_with_filename() {
+        let path = CWideString::new("C:\\foo\\bar.txt").expect("invalid c wide string");
+        let (path, offset) = get_full_path_name(&path).expect("failed to get full path name");
+        let offset = offset.expect("missing filename");
+        assert_eq!(
+            String::from_utf16_lossy(&path.as_slice()[offset..]),
+            "bar.txt"
+        );
+    }
+
+    #[test]
+    fn full_path_name_without_filename() {
+        let path = CWideString::new("C:\\foo\\").expect("invalid c wide string");
+        let (path, offset) = get_full_path_name(&path).expect("failed to get full path name");
+        assert_eq!(String::from_utf16_lossy(path.as_slice()), "C:\\foo\\");
+        assert_eq!(offset, None);
+    }
+
+    #[test]
+    fn full_path_name_longer_than_max_path() {
+        let long = format!("C:\\{}\\file.txt", "a".repeat(MAX_PATH));
+        let path = CWideString::new(long.as_str()).expect("invalid c wide string");
+        let (path, offset) = get_full_path_name(&path).expect("failed to get full path name");
+        assert_eq!(String::from_utf16_lossy(path.as_slice()), long);
+        assert_eq!(offset, Some(long.len() - "file.txt".len()));
+    }
+
+    #[test]
+    fn filename_offset_bounds() {
+        let path = [0_u16; 8];
+        let base = path.as_ptr();
+
+        assert_eq!(filename_offset(base, std::ptr::null(), 7), None);
+        assert_eq!(filename_offset(base, base.wrapping_add(3), 7), Some(3));
+        assert_eq!(filename_offset(base, base.wrapping_add(7), 7), None);
+        assert_eq!(filename_offset(base.wrapping_add(1), base, 7), None);
+    }
+
+    #[test]
+    fn final_path_name_by_handle() {
+        use std::os::windows::io::AsRawHandle;
+
+        let file = std::fs::File::open("Cargo.toml").expect("failed to open file");
+        let path = unsafe { get_final_path_name_by_handle(file.as_raw_handle().cast()) }
+            .expect("failed to get final path name");
+        let path = String::from_utf16_lossy(path.as_slice());
+        assert!(
+            path.starts_with("\\\\?\\"),
+            "'{}' is not a verbatim path",
+            path
+        );
+        assert!(path.ends_with("\\Cargo.toml"));
+    }
+
+    #[test]
+    fn logical_drive_strings() {
+        let drives = get_logical_drive_strings().expect("failed to get drives");
+        assert!(!drives.is_empty());
+        for drive in drives.iter() {
+            let drive = String::from_utf16_lossy(drive.as_slice());
+            assert!(drive.ends_with(":\\"), "'{}' is not a drive root", drive);
+        }
+    }
+}
@@ -1,292 +1,270 @@
+/// Emit a debug-level log record if the `log` feature is enabled.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        {
+            log::debug!($($arg)*);
+        }
+    };
+}
+
+pub mod builder;
 pub mod c_wide_string;
+pub mod combaseapi;
 pub mod fileapi;
+pub mod prelude;
 pub mod shobjidl;
-
+mod sysinfoapi;
+pub mod winbase;
+mod winuser;
+
+pub use self::builder::AsyncDialog;
+pub use self::builder::CancelHandle;
+pub use self::builder::ConfigureFn;
+pub use self::builder::DialogConfig;
+pub use self::builder::DialogMode;
+pub use self::builder::DialogOutcome;
+pub use self::builder::DialogResult;
+pub use self::builder::FileDialogBackend;
+pub use self::builder::FileDialogBuilder;
+pub use self::builder::FileOpenDialogBuilder;
+pub use self::builder::FileSaveDialogBuilder;
+pub use self::builder::IntoHwnd;
+pub use self::builder::Open;
+pub use self::builder::Save;
 pub use self::c_wide_string::CWideStr;
 pub use self::c_wide_string::CWideString;
+pub use self::c_wide_string::CWideStringBuilder;
+pub use self::c_wide_string::CoTaskMemWideStringExt;
+pub use self::c_wide_string::IntoChars;
 pub use self::c_wide_string::NulError;
+pub use self::combaseapi::init_com_scoped;
+pub use self::combaseapi::init_com_scoped_with;
+pub use self::combaseapi::ComGuard;
+pub use self::combaseapi::ComInit;
+pub use self::fileapi::get_final_path_name_by_handle;
 pub use self::fileapi::get_full_path_name;
+pub use self::fileapi::get_logical_drive_strings;
+pub use self::shobjidl::enumerate_drives;
 pub use self::shobjidl::DisplayNameType;
 pub use self::shobjidl::FileDialog;
+pub use self::shobjidl::FileDialogCustomize;
+pub use self::shobjidl::FileDialogEvents;
+pub use self::shobjidl::FileFilterError;
 pub use self::shobjidl::FileFilters;
 pub use self::shobjidl::FileOpenDialog;
 pub use self::shobjidl::FileSaveDialog;
+pub use self::shobjidl::KnownFolder;
 pub use self::shobjidl::ModalWindow;
+pub use self::shobjidl::ParseFlags;
+pub use self::shobjidl::ShellFolder;
 pub use self::shobjidl::ShellItem;
+pub use self::shobjidl::ShellItemArray;
+pub use self::shobjidl::Stream;
+pub use self::shobjidl::UnknownDisplayNameType;
+pub use self::shobjidl::KNOWN_EXTENSIONS;
+pub use self::winbase::format_message;
+pub use self::winbase::Win32Error;
 pub use skylight::CoTaskMemWideString;
 pub use skylight::HResult;
-use std::borrow::Cow;
-use std::ffi::OsStr;
-use std::path::Path;
 use std::path::PathBuf;
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::ERROR_CANCELLED;
+use winapi::shared::winerror::FACILITY_WIN32;
+use winapi::shared::winerror::HRESULT_CODE;
+use winapi::shared::winerror::HRESULT_FACILITY;
+use winapi::shared::winerror::HRESULT_FROM_WIN32;
 
 /// An error  that may occur during the use of a file dialog
 #[derive(Debug, thiserror::Error)]
 pub enum NfdError {
     /// An API call failed
-    #[error(transparent)]
+    #[error("{}", winbase::describe_hresult(.0))]
     HResult(#[from] skylight::HResult),
 
+    /// An API call failed
+    #[error("{op} failed: {}", winbase::describe_hresult(.hr))]
+    HResultContext {
+        /// The name of the API call that failed
+        op: &'static str,
+
+        /// The error
+        #[source]
+        hr: skylight::HResult,
+    },
+
+    /// A closure added with [`FileDialogBuilder::configure`] failed
+    #[error("configure closure {index} failed: {}", winbase::describe_hresult(.hr))]
+    Configure {
+        /// The index of the closure, in the order they were added
+        index: usize,
+
+        /// The error
+        #[source]
+        hr: skylight::HResult,
+    },
+
+    /// COM was not initialized on the thread that built the dialog
+    #[error("COM is not initialized on this thread; call `init_com` or `with_thread_com` on the builder, or initialize COM yourself")]
+    ComNotInitialized(#[source] skylight::HResult),
+
+    /// The selected item can not be opened as a stream
+    #[error("the selected item does not support being opened as a stream")]
+    StreamUnsupported(#[source] skylight::HResult),
+
+    /// More items were selected than allowed
+    #[error("{count} items were selected, but at most {max} are allowed")]
+    TooManyItems {
+        /// The number of selected items
+        count: usize,
+
+        /// The maximum number of items
+        max: usize,
+    },
+
+    /// The parent window handle was invalid
+    #[error("the parent window handle is invalid")]
+    InvalidParent,
+
+    /// A selected path did not end in a filename
+    #[error("the path '{}' has no filename", .0.display())]
+    MissingFilename(PathBuf),
+
+    /// An I/O error occurred
+    #[error("an I/O error occurred")]
+    Io(#[from] std::io::Error),
+
+    /// An [`AsyncDialog`] was cancelled
+    #[error("the dialog was cancelled")]
+    Cancelled,
+
+    /// [`FileDialogBuilder::execute_async`] was used with closures added by [`FileDialogBuilder::configure`]
+    #[error("configure closures can not be sent to another thread, so they can not be used with execute_async")]
+    AsyncConfigure,
+
     /// A string contained an interior NUL
     #[error("a string contained an interior NUL")]
     NulError(#[from] NulError),
-}
-
-/// Builder for a [`FileOpenDialog`]
-pub struct FileOpenDialogBuilder<'a, 'b, 'c> {
-    /// Whether to init com
-    pub init_com: bool,
-
-    /// Path to open by default
-    pub default_path: Option<&'a Path>,
-
-    /// Path to open, regardless of past choices
-    pub path: Option<&'b Path>,
 
-    /// File types
-    pub filetypes: FileFilters<'static>,
-
-    /// Filename
-    pub filename: Option<&'c OsStr>,
+    /// A selected path was not valid UTF-8
+    #[cfg(feature = "camino")]
+    #[error("the path '{}' is not valid UTF-8", .0.display())]
+    NonUtf8Path(PathBuf),
 }
 
-impl<'a, 'b, 'c> FileOpenDialogBuilder<'a, 'b, 'c> {
-    /// Make a new [`FileOpenDialogBuilder`].
-    pub fn new() -> Self {
-        FileOpenDialogBuilder {
-            init_com: false,
-            default_path: None,
-            path: None,
-            filetypes: FileFilters::new(),
-            filename: None,
+impl NfdError {
+    /// Make a function that wraps an [`HResult`] with the name of the API call that produced it.
+    fn context(op: &'static str) -> impl FnOnce(HResult) -> Self {
+        move |hr| {
+            debug!("{} failed: {}", op, hr);
+            NfdError::HResultContext { op, hr }
         }
     }
 
-    /// Whether to init com
-    pub fn init_com(&mut self) -> &mut Self {
-        self.init_com = true;
-        self
-    }
-
-    /// Set the default path where the dialog will open
-    pub fn default_path(&mut self, default_path: &'a Path) -> &mut Self {
-        self.default_path = Some(default_path);
-        self
-    }
-
-    /// Set the path where the dialog will open
-    pub fn path(&mut self, path: &'b Path) -> &mut Self {
-        self.path = Some(path);
-        self
-    }
-
-    /// Add a file type.
-    ///
-    /// # Panics
-    /// Panics if the name of filter contain an interior NUL.
-    pub fn filetype(&mut self, name: &OsStr, filter: &OsStr) -> &mut Self {
-        let name = Cow::Owned(CWideString::new(name).expect("name contained an interior NUL"));
-        let filter =
-            Cow::Owned(CWideString::new(filter).expect("filter contained an interior NUL"));
-        self.filetypes.add_filter(name, filter);
-        self
-    }
-
-    /// Set the default filename
-    pub fn filename(&mut self, filename: &'c OsStr) -> &mut Self {
-        self.filename = Some(filename);
-        self
-    }
-
-    /// Build a dialog.
-    pub fn build(&self) -> Result<FileOpenDialog, NfdError> {
-        if self.init_com {
-            skylight::init_mta_com_runtime()?;
-        }
-
-        let dialog = FileOpenDialog::new()?;
-
-        if let Some(default_path) = self.default_path {
-            let shell_item = ShellItem::from_path(default_path)?;
-            dialog.set_default_folder(shell_item)?;
-        }
-
-        if let Some(path) = self.path {
-            let shell_item = ShellItem::from_path(path)?;
-            dialog.set_folder(shell_item)?;
-        }
-
-        if !self.filetypes.is_empty() {
-            dialog.set_filetypes(&self.filetypes)?;
-        }
-
-        if let Some(filename) = self.filename {
-            let filename = CWideString::new(filename)?;
-            dialog.set_filename(&filename)?;
+    /// Get the closest [`std::io::ErrorKind`] for this error.
+    fn io_error_kind(&self) -> std::io::ErrorKind {
+        match self {
+            NfdError::HResult(hr)
+            | NfdError::HResultContext { hr, .. }
+            | NfdError::Configure { hr, .. }
+            | NfdError::ComNotInitialized(hr)
+            | NfdError::StreamUnsupported(hr) => hresult_io_error_kind(hr),
+            NfdError::TooManyItems { .. } | NfdError::MissingFilename(_) => {
+                std::io::ErrorKind::InvalidInput
+            }
+            NfdError::InvalidParent => std::io::ErrorKind::InvalidInput,
+            NfdError::Cancelled => std::io::ErrorKind::Interrupted,
+            NfdError::AsyncConfigure => std::io::ErrorKind::InvalidInput,
+            NfdError::Io(e) => e.kind(),
+            NfdError::NulError(_) => std::io::ErrorKind::InvalidInput,
+            #[cfg(feature = "camino")]
+            NfdError::NonUtf8Path(_) => std::io::ErrorKind::InvalidData,
         }
-
-        Ok(dialog)
-    }
-
-    /// Execute a dialog.
-    pub fn execute(&self) -> Result<PathBuf, NfdError> {
-        let dialog = self.build()?;
-
-        dialog.show(None)?;
-        let shellitem = dialog.get_result()?;
-
-        Ok(PathBuf::from(
-            shellitem
-                .get_display_name(DisplayNameType::FileSysPath)?
-                .as_os_string(),
-        ))
     }
 }
 
-impl Default for FileOpenDialogBuilder<'_, '_, '_> {
-    fn default() -> Self {
-        FileOpenDialogBuilder::new()
+/// Get the closest [`std::io::ErrorKind`] for an [`HResult`].
+///
+/// A cancelled dialog is [`std::io::ErrorKind::Interrupted`].
+pub(crate) fn hresult_io_error_kind(hr: &HResult) -> std::io::ErrorKind {
+    if hr.0 == HRESULT_FROM_WIN32(ERROR_CANCELLED) {
+        return std::io::ErrorKind::Interrupted;
     }
-}
-
-/// Builder for a FileSaveDialog
-pub struct FileSaveDialogBuilder<'a, 'b, 'c> {
-    /// Whether to init com
-    pub init_com: bool,
-
-    /// Path to open by default
-    pub default_path: Option<&'a Path>,
 
-    /// Path to open, regardless of past choices
-    pub path: Option<&'b Path>,
-
-    /// File types
-    pub filetypes: FileFilters<'static>,
-
-    /// Filename
-    pub filename: Option<&'c OsStr>,
-}
-
-impl<'a, 'b, 'c> FileSaveDialogBuilder<'a, 'b, 'c> {
-    /// Make a new FileSaveDialogBuilder
-    pub fn new() -> Self {
-        FileSaveDialogBuilder {
-            init_com: false,
-            default_path: None,
-            path: None,
-            filetypes: FileFilters::new(),
-            filename: None,
-        }
+    // Wrapped Win32 errors can be mapped by std.
+    if HRESULT_FACILITY(hr.0) == FACILITY_WIN32 {
+        return std::io::Error::from_raw_os_error(HRESULT_CODE(hr.0)).kind();
     }
 
-    /// Whether to init com
-    pub fn init_com(&mut self) -> &mut Self {
-        self.init_com = true;
-        self
-    }
-
-    /// Set the default path where the dialog will open
-    pub fn default_path(&mut self, default_path: &'a Path) -> &mut Self {
-        self.default_path = Some(default_path);
-        self
-    }
-
-    /// Set the path where the dialog will open
-    pub fn path(&mut self, path: &'b Path) -> &mut Self {
-        self.path = Some(path);
-        self
-    }
+    std::io::ErrorKind::Other
+}
 
-    /// Add a file type.
+impl From<NfdError> for std::io::Error {
+    /// Convert an [`NfdError`] into an [`std::io::Error`].
     ///
-    /// # Panics
-    /// Panics if the name of filter contain an interior NUL.
-    pub fn filetype(&mut self, name: &OsStr, filter: &OsStr) -> &mut Self {
-        let name = Cow::Owned(CWideString::new(name).expect("name contained an interior NUL"));
-        let filter =
-            Cow::Owned(CWideString::new(filter).expect("filter contained an interior NUL"));
-        self.filetypes.add_filter(name, filter);
-        self
-    }
-
-    /// Set the default filename
-    pub fn filename(&mut self, filename: &'c OsStr) -> &mut Self {
-        self.filename = Some(filename);
-        self
-    }
-
-    /// Build a dialog.
-    pub fn build(&self) -> Result<FileSaveDialog, NfdError> {
-        if self.init_com {
-            skylight::init_mta_com_runtime()?;
-        }
-
-        let dialog = FileSaveDialog::new()?;
-
-        if let Some(default_path) = self.default_path {
-            let shell_item = ShellItem::from_path(default_path)?;
-            dialog.set_default_folder(shell_item)?;
-        }
-
-        if let Some(path) = self.path {
-            let shell_item = ShellItem::from_path(path)?;
-            dialog.set_folder(shell_item)?;
+    /// The [`NfdError`] is kept as the inner error, so the original [`HResult`] stays in the source chain.
+    /// [`NfdError::Io`] is unwrapped instead.
+    fn from(error: NfdError) -> Self {
+        match error {
+            NfdError::Io(error) => error,
+            error => std::io::Error::new(error.io_error_kind(), error),
         }
-
-        if !self.filetypes.is_empty() {
-            dialog.set_filetypes(&self.filetypes)?;
-        }
-
-        if let Some(filename) = self.filename {
-            let filename = CWideString::new(filename)?;
-            dialog.set_filename(&filename)?;
-        }
-
-        Ok(dialog)
-    }
-
-    /// Execute a dialog.
-    pub fn execute(&self) -> Result<PathBuf, NfdError> {
-        let dialog = self.build()?;
-
-        dialog.show(None)?;
-        let shellitem = dialog.get_result()?;
-
-        Ok(PathBuf::from(
-            shellitem
-                .get_display_name(DisplayNameType::FileSysPath)?
-                .as_os_string(),
-        ))
-    }
-}
-
-impl Default for FileSaveDialogBuilder<'_, '_, '_> {
-    fn default() -> Self {
-        Self::new()
     }
 }
 
 /// Default nfd open dialog.
 /// Look at this functions impl and write your own if you need more control
+///
+/// Only items with a file system path can be selected, since a path is returned.
 pub fn nfd_open() -> Result<PathBuf, NfdError> {
-    FileOpenDialogBuilder::new().init_com().execute()
+    FileOpenDialogBuilder::new()
+        .init_com()
+        .force_filesystem()
+        .execute()
 }
 
 /// Default nfd save dialog.
 /// Look at this functions impl and write your own if you need more control
+///
+/// Only items with a file system path can be selected, since a path is returned.
 pub fn nfd_save() -> Result<PathBuf, NfdError> {
-    FileSaveDialogBuilder::new().init_com().execute()
+    FileSaveDialogBuilder::new()
+        .init_com()
+        .force_filesystem()
+        .execute()
+}
+
+/// Default nfd open dialog, modal to the given window.
+///
+/// Only items with a file system path can be selected, since a path is returned.
+pub fn nfd_open_with_parent(parent: HWND) -> Result<PathBuf, NfdError> {
+    FileOpenDialogBuilder::new()
+        .init_com()
+        .force_filesystem()
+        .parent(parent)
+        .execute()
+}
+
+/// Default nfd save dialog, modal to the given window.
+///
+/// Only items with a file system path can be selected, since a path is returned.
+pub fn nfd_save_with_parent(parent: HWND) -> Result<PathBuf, NfdError> {
+    FileSaveDialogBuilder::new()
+        .init_com()
+        .force_filesystem()
+        .parent(parent)
+        .execute()
 }
 
 /// Shothand for `FileOpenDialogBuilder::new().init_com()`
-pub fn nfd_open_builder<'a, 'b, 'c>() -> FileOpenDialogBuilder<'a, 'b, 'c> {
+pub fn nfd_open_builder() -> FileOpenDialogBuilder {
     let mut builder = FileOpenDialogBuilder::new();
     builder.init_com();
     builder
 }
 
 /// Shothand for `FileSaveDialogBuilder::new().init_com()`
-pub fn nfd_save_builder<'a, 'b, 'c>() -> FileSaveDialogBuilder<'a, 'b, 'c> {
+pub fn nfd_save_builder() -> FileSaveDialogBuilder {
     let mut builder = FileSaveDialogBuilder::new();
     builder.init_com();
     builder
@@ -308,6 +286,76 @@ mod tests {
         }
     }
 
+    #[test]
+    fn builder_clone_is_independent() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder.filetype("toml", "*.toml");
+
+        let mut cloned = builder.clone();
+        cloned.filetype("txt", "*.txt").filename("Cargo.toml");
+
+        assert_eq!(builder.filetypes.len(), 1);
+        assert!(builder.filename.is_none());
+        assert_eq!(cloned.filetypes.len(), 2);
+        assert!(cloned.filename.is_some());
+    }
+
+    #[test]
+    fn builder_into_save_and_back() {
+        let mut builder = FileOpenDialogBuilder::new();
+        builder
+            .init_com()
+            .path(".".as_ref())
+            .filetype("toml", "*.toml")
+            .filename("Cargo.toml");
+
+        let builder = builder.into_save();
+        assert!(builder.init_com);
+        assert!(builder.path.is_some());
+        assert_eq!(builder.filetypes.len(), 1);
+        assert!(builder.filename.is_some());
+
+        let builder = builder.into_open();
+        assert!(builder.init_com);
+        assert!(builder.path.is_some());
+        assert_eq!(builder.filetypes.len(), 1);
+        assert!(builder.filename.is_some());
+    }
+
+    #[test]
+    fn save_only_options_dropped_on_into_open() {
+        let mut builder = FileSaveDialogBuilder::new();
+        builder.default_extension("txt");
+        assert!(builder.default_extension.is_some());
+
+        let builder = builder.into_open();
+        assert!(builder.default_extension.is_none());
+    }
+
+    #[test]
+    fn nfd_error_into_io_error() {
+        let error = std::io::Error::from(NfdError::from(HResult::from(HRESULT_FROM_WIN32(
+            ERROR_CANCELLED,
+        ))));
+        assert_eq!(error.kind(), std::io::ErrorKind::Interrupted);
+
+        let code = HRESULT_FROM_WIN32(2);
+        let error = std::io::Error::from(NfdError::context("GetResult")(HResult::from(code)));
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        let source = std::error::Error::source(error.get_ref().expect("missing inner error"))
+            .expect("missing source")
+            .downcast_ref::<HResult>()
+            .expect("source is not an HResult");
+        assert_eq!(source.0, code);
+
+        let error = std::io::Error::from(NfdError::MissingFilename(PathBuf::from("C:\\")));
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        let error = std::io::Error::from(NfdError::Io(std::io::ErrorKind::AlreadyExists.into()));
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert!(error.get_ref().is_none());
+    }
+
     #[test]
     #[ignore]
     fn it_works_open_default() {
@@ -328,14 +376,119 @@ mod tests {
             .init_com()
             .default_path(".".as_ref())
             .path(".".as_ref())
-            .filetype("toml".as_ref(), "*.toml".as_ref())
-            .filetype("sks".as_ref(), "*.txt;*.lbl".as_ref())
+            .filetype("toml", "*.toml")
+            .filetype("sks", "*.txt;*.lbl")
             .execute()
             .expect("file dialog failed to execute");
 
         println!("Open File Path (builder): {}", path.display());
     }
 
+    #[test]
+    #[ignore]
+    fn it_works_open_multiple() {
+        set_dpi();
+
+        let paths = FileOpenDialogBuilder::new()
+            .init_com()
+            .path(".".as_ref())
+            .max_selection(3)
+            .execute_multiple()
+            .expect("file dialog failed to execute");
+
+        for path in paths {
+            println!("Open File Path (multiple): {}", path.display());
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn it_works_open_optional() {
+        set_dpi();
+
+        let outcome = FileOpenDialogBuilder::new()
+            .init_com()
+            .filetype("toml", "*.toml")
+            .filetype("sks", "*.txt;*.lbl")
+            .execute_optional_detailed()
+            .expect("file dialog failed to execute");
+
+        println!(
+            "Open File Path (optional): {:?} (file type: {})",
+            outcome.path, outcome.filetype_index
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn it_works_open_async_cancel() {
+        use std::future::Future;
+        use std::sync::Arc;
+        use std::task::Context;
+        use std::task::Poll;
+
+        struct ThreadWaker(std::thread::Thread);
+
+        impl std::task::Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        set_dpi();
+
+        let dialog = FileOpenDialogBuilder::new()
+            .path(".".as_ref())
+            .execute_async();
+        let cancel = dialog.cancel_handle();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_secs(1));
+            cancel.cancel();
+        });
+
+        let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut dialog = Box::pin(dialog);
+        let result = loop {
+            if let Poll::Ready(result) = dialog.as_mut().poll(&mut cx) {
+                break result;
+            }
+            std::thread::park();
+        };
+        assert!(matches!(result, Err(NfdError::Cancelled)));
+    }
+
+    #[test]
+    #[ignore]
+    fn it_works_open_as() {
+        set_dpi();
+
+        let path = FileOpenDialogBuilder::new()
+            .init_com()
+            .execute_as(DisplayNameType::DesktopAbsoluteParsing)
+            .expect("file dialog failed to execute");
+
+        println!("Open File Path (parsing name): {}", path.display());
+    }
+
+    #[test]
+    #[ignore]
+    fn it_works_open_into() {
+        set_dpi();
+
+        let mut buf = Vec::new();
+        FileOpenDialogBuilder::new()
+            .init_com()
+            .execute_into(&mut buf)
+            .expect("file dialog failed to execute");
+        assert_eq!(buf.last(), Some(&0));
+
+        println!(
+            "Open File Path (into): {}",
+            String::from_utf16_lossy(&buf[..buf.len() - 1])
+        );
+    }
+
     #[test]
     #[ignore]
     fn it_works_save_default() {
@@ -347,6 +500,25 @@ mod tests {
         );
     }
 
+    #[test]
+    #[ignore]
+    fn it_works_save_detailed() {
+        set_dpi();
+
+        let result = FileSaveDialogBuilder::new()
+            .init_com()
+            .path(".".as_ref())
+            .filename("Cargo.toml")
+            .execute_detailed()
+            .expect("file dialog failed to execute");
+
+        println!(
+            "Save File Path (detailed): {} (exists: {})",
+            result.path.display(),
+            result.exists
+        );
+    }
+
     #[test]
     #[ignore]
     fn it_works_save() {
@@ -356,9 +528,9 @@ mod tests {
             .init_com()
             .default_path(".".as_ref())
             .path(".".as_ref())
-            .filetype("toml".as_ref(), "*.toml".as_ref())
-            .filetype("sks".as_ref(), "*.txt;*.lbl".as_ref())
-            .filename("level.txt".as_ref())
+            .filetype("toml", "*.toml")
+            .filetype("sks", "*.txt;*.lbl")
+            .filename("level.txt")
             .execute()
             .expect("file dialog failed to exececute");
 
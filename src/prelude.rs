@@ -0,0 +1,26 @@
+//! Commonly used types and functions.
+//!
+//! ```
+//! use win_nfd::prelude::*;
+//! ```
+
+pub use crate::nfd_open;
+pub use crate::nfd_open_builder;
+pub use crate::nfd_open_with_parent;
+pub use crate::nfd_save;
+pub use crate::nfd_save_builder;
+pub use crate::nfd_save_with_parent;
+pub use crate::CWideStr;
+pub use crate::CWideString;
+pub use crate::DialogOutcome;
+pub use crate::DialogResult;
+pub use crate::DisplayNameType;
+pub use crate::FileDialog;
+pub use crate::FileFilters;
+pub use crate::FileOpenDialog;
+pub use crate::FileOpenDialogBuilder;
+pub use crate::FileSaveDialog;
+pub use crate::FileSaveDialogBuilder;
+pub use crate::HResult;
+pub use crate::NfdError;
+pub use crate::ShellItem;
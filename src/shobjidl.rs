@@ -1,28 +1,76 @@
+use crate::fileapi::get_final_path_name_by_handle;
+use crate::fileapi::get_logical_drive_strings;
 use crate::get_full_path_name;
+use crate::hresult_io_error_kind;
 use crate::CWideStr;
 use crate::CWideString;
+use crate::NfdError;
+use crate::NulError;
+use crate::Win32Error;
 use skylight::CoTaskMemWideString;
 use skylight::HResult;
 use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::convert::TryInto;
+use std::fs::File;
 use std::ops::Deref;
 use std::os::raw::c_void;
+use std::os::windows::io::AsRawHandle;
 use std::path::Path;
+use std::path::PathBuf;
 use std::ptr::NonNull;
+use winapi::shared::guiddef::GUID;
 use winapi::shared::guiddef::REFIID;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::minwindef::UINT;
+use winapi::shared::minwindef::ULONG;
 use winapi::shared::ntdef::HRESULT;
 use winapi::shared::ntdef::PCWSTR;
 use winapi::shared::windef::HWND;
+use winapi::shared::winerror::ERROR_CANCELLED;
+use winapi::shared::winerror::E_INVALIDARG;
+use winapi::shared::winerror::E_NOINTERFACE;
+use winapi::shared::winerror::E_NOTIMPL;
+use winapi::shared::winerror::E_UNEXPECTED;
 use winapi::shared::winerror::FAILED;
+use winapi::shared::winerror::HRESULT_FROM_WIN32;
+use winapi::shared::winerror::S_FALSE;
+use winapi::shared::winerror::S_OK;
+use winapi::shared::wtypes::STGC_DEFAULT;
+use winapi::um::combaseapi::CoTaskMemFree;
 use winapi::um::combaseapi::CLSCTX_ALL;
+use winapi::um::knownfolders::FOLDERID_Desktop;
+use winapi::um::knownfolders::FOLDERID_Documents;
+use winapi::um::knownfolders::FOLDERID_Downloads;
+use winapi::um::knownfolders::FOLDERID_Music;
+use winapi::um::knownfolders::FOLDERID_Pictures;
+use winapi::um::knownfolders::FOLDERID_Profile;
+use winapi::um::knownfolders::FOLDERID_Videos;
+use winapi::um::objbase::STGM_CREATE;
+use winapi::um::objbase::STGM_WRITE;
+use winapi::um::objidl::IBindCtx;
+use winapi::um::objidl::BIND_OPTS;
+use winapi::um::objidlbase::IStream;
+use winapi::um::oleidl::IOleWindow;
 use winapi::um::shobjidl::IFileDialog;
+use winapi::um::shobjidl::IFileDialog2;
+use winapi::um::shobjidl::IFileDialogCustomize;
 use winapi::um::shobjidl::IFileOpenDialog;
 use winapi::um::shobjidl::IFileSaveDialog;
+use winapi::um::shobjidl::FDAP_BOTTOM;
+use winapi::um::shobjidl::FDAP_TOP;
+use winapi::um::shobjidl::FILEOPENDIALOGOPTIONS;
 use winapi::um::shobjidl_core::CLSID_FileOpenDialog;
 use winapi::um::shobjidl_core::CLSID_FileSaveDialog;
+use winapi::um::shobjidl_core::IEnumIDList;
+use winapi::um::shobjidl_core::IEnumShellItems;
 use winapi::um::shobjidl_core::IModalWindow;
+use winapi::um::shobjidl_core::IShellFolder;
 use winapi::um::shobjidl_core::IShellItem;
+use winapi::um::shobjidl_core::IShellItemArray;
 use winapi::um::shobjidl_core::SHCreateItemFromParsingName;
+use winapi::um::shobjidl_core::SHCONTF_FOLDERS;
+use winapi::um::shobjidl_core::SHCONTF_NONFOLDERS;
 use winapi::um::shobjidl_core::SIGDN;
 use winapi::um::shobjidl_core::SIGDN_DESKTOPABSOLUTEEDITING;
 use winapi::um::shobjidl_core::SIGDN_DESKTOPABSOLUTEPARSING;
@@ -35,15 +83,43 @@ use winapi::um::shobjidl_core::SIGDN_PARENTRELATIVEFORUI;
 use winapi::um::shobjidl_core::SIGDN_PARENTRELATIVEPARSING;
 use winapi::um::shobjidl_core::SIGDN_URL;
 use winapi::um::shtypes::COMDLG_FILTERSPEC;
+use winapi::um::shtypes::KNOWNFOLDERID;
 use winapi::um::shtypes::PCIDLIST_ABSOLUTE;
+use winapi::um::shtypes::PCUITEMID_CHILD;
 use winapi::um::shtypes::PIDLIST_ABSOLUTE;
 use winapi::um::shtypes::PIDLIST_RELATIVE;
+use winapi::um::shtypes::REFKNOWNFOLDERID;
+use winapi::um::unknwnbase::IUnknown;
+use winapi::um::winnt::HANDLE;
 use winapi::Interface;
 
+mod file_dialog_events;
+mod shell_item_filter;
+
+pub use self::file_dialog_events::FileDialogEvents;
+
 #[repr(transparent)]
 pub struct ModalWindow(NonNull<IModalWindow>);
 
 impl ModalWindow {
+    /// Make a new [`ModalWindow`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IModalWindow` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IModalWindow>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IModalWindow {
+        self.0.as_ptr()
+    }
+
     /// Show the window
     pub fn show(&self, parent: Option<HWND>) -> Result<(), HResult> {
         let ret = unsafe { self.0.as_ref().Show(parent.unwrap_or(std::ptr::null_mut())) };
@@ -54,6 +130,21 @@ impl ModalWindow {
             Ok(())
         }
     }
+
+    /// Show the window, returning `false` if the user cancelled it.
+    pub fn show_optional(&self, parent: Option<HWND>) -> Result<bool, HResult> {
+        let ret = unsafe { self.0.as_ref().Show(parent.unwrap_or(std::ptr::null_mut())) };
+
+        if ret == HRESULT_FROM_WIN32(ERROR_CANCELLED) {
+            return Ok(false);
+        }
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(true)
+    }
 }
 
 impl Drop for ModalWindow {
@@ -68,6 +159,24 @@ impl Drop for ModalWindow {
 pub struct FileDialog(NonNull<IFileDialog>);
 
 impl FileDialog {
+    /// Make a new [`FileDialog`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IFileDialog` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IFileDialog>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IFileDialog {
+        self.0.as_ptr()
+    }
+
     /// Set the default folder
     pub fn set_default_folder(&self, item: ShellItem) -> Result<(), HResult> {
         let ret = unsafe { self.0.as_ref().SetDefaultFolder(item.0.as_ptr()) };
@@ -96,13 +205,19 @@ impl FileDialog {
 
     /// Set the file types
     ///
-    /// # Panics
-    /// Panics if the number of filters cannot fit in a usize.
+    /// Windows only accepts this once per dialog, before it is shown.
+    /// See [`FileDialog::update_filetypes`] to handle later calls gracefully.
+    /// This does nothing if `filters` is empty, since Windows may reject an empty list.
+    ///
+    /// # Errors
+    /// Returns `E_INVALIDARG` if the number of filters cannot fit in a u32.
+    /// Returns `E_UNEXPECTED` if the file types were already set.
     pub fn set_filetypes(&self, filters: &FileFilters) -> Result<(), HResult> {
-        let filters_len = filters
-            .len()
-            .try_into()
-            .expect("length is longer than a u32");
+        if filters.is_empty() {
+            return Ok(());
+        }
+
+        let filters_len = filter_count(filters.len())?;
 
         // Alright, I'm *fairly* certain this performs a deep copy so I can free filters immediately.
         // Even though some projects like
@@ -132,9 +247,28 @@ impl FileDialog {
         Ok(())
     }
 
-    /// Set filename
-    pub fn set_filename(&self, filename: &CWideStr) -> Result<(), HResult> {
-        let ret = unsafe { self.0.as_ref().SetFileName(filename.as_ptr()) };
+    /// Try to replace the file types, for filters that are built lazily before the dialog is shown.
+    ///
+    /// Windows ignores changes to the file types after the dialog is shown,
+    /// and may reject them after the first call to [`FileDialog::set_filetypes`].
+    /// Returns `Ok(false)` if the file types were rejected for those reasons, leaving the old ones in place.
+    ///
+    /// # Errors
+    /// Returns `E_INVALIDARG` if the number of filters cannot fit in a u32.
+    pub fn update_filetypes(&self, filters: &FileFilters) -> Result<bool, HResult> {
+        match self.set_filetypes(filters) {
+            Ok(()) => Ok(true),
+            Err(e) if e.0 == E_UNEXPECTED => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Close the dialog while it is shown, making [`ModalWindow::show`] return `hr` as an error.
+    ///
+    /// This must be called on the thread that showed the dialog, usually from a [`FileDialogEvents`] handler.
+    /// It has no effect if the dialog is not shown.
+    pub fn close(&self, hr: HRESULT) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().Close(hr) };
 
         if FAILED(ret) {
             return Err(HResult::from(ret));
@@ -143,21 +277,23 @@ impl FileDialog {
         Ok(())
     }
 
-    /// Get single result
-    pub fn get_result(&self) -> Result<ShellItem, HResult> {
-        let mut ptr = std::ptr::null_mut();
-        let ret = unsafe { self.0.as_ref().GetResult(&mut ptr) };
+    /// Get the options
+    pub fn get_options(&self) -> Result<FILEOPENDIALOGOPTIONS, HResult> {
+        let mut options = 0;
+        let ret = unsafe { self.0.as_ref().GetOptions(&mut options) };
 
         if FAILED(ret) {
             return Err(HResult::from(ret));
         }
-        let ptr = NonNull::new(ptr).expect("ptr was null");
-        Ok(ShellItem(ptr))
+
+        Ok(options)
     }
 
-    /// Show the window
-    pub fn show(&self, parent: Option<HWND>) -> Result<(), HResult> {
-        let ret = unsafe { self.0.as_ref().Show(parent.unwrap_or(std::ptr::null_mut())) };
+    /// Set the options
+    ///
+    /// This replaces all options, so use [`FileDialog::get_options`] first to preserve the defaults.
+    pub fn set_options(&self, options: FILEOPENDIALOGOPTIONS) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().SetOptions(options) };
 
         if FAILED(ret) {
             return Err(HResult::from(ret));
@@ -165,236 +301,1847 @@ impl FileDialog {
 
         Ok(())
     }
-}
-
-impl Deref for FileDialog {
-    type Target = ModalWindow;
 
-    fn deref(&self) -> &Self::Target {
-        // Safety:
-        // ModalWindow's repr is a subset of FileDialog's.
-        unsafe { std::mem::transmute::<&FileDialog, &ModalWindow>(self) }
-    }
-}
+    /// Set filename
+    pub fn set_filename(&self, filename: &CWideStr) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().SetFileName(filename.as_ptr()) };
 
-impl Drop for FileDialog {
-    fn drop(&mut self) {
-        unsafe {
-            self.0.as_ref().Release();
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
         }
-    }
-}
-
-/// A File Open Dialog
-#[repr(transparent)]
-pub struct FileOpenDialog(NonNull<IFileOpenDialog>);
 
-impl FileOpenDialog {
-    /// Make a new [`FileOpenDialog`].
-    pub fn new() -> Result<Self, HResult> {
-        let ptr = unsafe { skylight::create_instance(&CLSID_FileOpenDialog, CLSCTX_ALL)? };
-        let ptr = NonNull::new(ptr).expect("ptr is null");
-        Ok(Self(ptr))
+        Ok(())
     }
-}
 
-impl Deref for FileOpenDialog {
-    type Target = FileDialog;
-
-    fn deref(&self) -> &Self::Target {
-        // Safety:
-        // FileDialog's repr is a subset of FileOpenDialog's.
-        unsafe { std::mem::transmute::<&FileOpenDialog, &FileDialog>(self) }
-    }
-}
+    /// Get the text currently in the filename box.
+    ///
+    /// This is the text as typed, so the default extension has not been appended yet.
+    pub fn get_filename(&self) -> Result<CoTaskMemWideString, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe { self.0.as_ref().GetFileName(&mut ptr) };
 
-impl Drop for FileOpenDialog {
-    fn drop(&mut self) {
-        unsafe {
-            self.0.as_ref().Release();
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
         }
+
+        let ptr = NonNull::new(ptr).expect("ptr was null");
+        Ok(unsafe { CoTaskMemWideString::from_raw(ptr) })
     }
-}
 
-/// A File Save Dialog
-#[repr(transparent)]
-pub struct FileSaveDialog(NonNull<IFileSaveDialog>);
+    /// Set the title of the dialog window.
+    pub fn set_title(&self, title: &CWideStr) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().SetTitle(title.as_ptr()) };
 
-impl FileSaveDialog {
-    /// Make a new [`FileSaveDialog`].
-    pub fn new() -> Result<Self, HResult> {
-        let ptr = unsafe { skylight::create_instance(&CLSID_FileSaveDialog, CLSCTX_ALL)? };
-        let ptr = NonNull::new(ptr).expect("ptr is null");
-        Ok(Self(ptr))
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
     }
-}
 
-impl Deref for FileSaveDialog {
-    type Target = FileDialog;
+    /// Set the label of the OK button, like "Open" or "Save".
+    pub fn set_ok_button_label(&self, label: &CWideStr) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().SetOkButtonLabel(label.as_ptr()) };
 
-    fn deref(&self) -> &Self::Target {
-        // Safety:
-        // FileDialog's repr is a subset of FileSaveDialog's.
-        unsafe { std::mem::transmute::<&FileSaveDialog, &FileDialog>(self) }
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
     }
-}
 
-impl Drop for FileSaveDialog {
-    fn drop(&mut self) {
-        unsafe {
-            self.0.as_ref().Release();
+    /// Set the extension to append to filenames entered without one.
+    ///
+    /// This should not include the leading `.`.
+    pub fn set_default_extension(&self, extension: &CWideStr) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().SetDefaultExtension(extension.as_ptr()) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
         }
-    }
-}
 
-/// File type filter list
-pub struct FileFilters<'s> {
-    filters: Vec<COMDLG_FILTERSPEC>,
+        Ok(())
+    }
 
-    storage: Vec<(Cow<'s, CWideStr>, Cow<'s, CWideStr>)>,
-}
+    /// Get the folder the dialog is currently showing.
+    ///
+    /// Before the dialog is shown, this is the folder it will open to.
+    /// Returns `None` if there is no folder yet.
+    pub fn get_folder(&self) -> Result<Option<ShellItem>, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe { self.0.as_ref().GetFolder(&mut ptr) };
 
-impl<'s> FileFilters<'s> {
-    /// Make an empty list of file type filters
-    pub fn new() -> Self {
-        Self {
-            filters: Vec::new(),
-            storage: Vec::new(),
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
         }
+
+        Ok(NonNull::new(ptr).map(ShellItem))
     }
 
-    /// Get the number of file filters
-    pub fn with_capacity(cap: usize) -> Self {
-        Self {
-            filters: Vec::with_capacity(cap),
-            storage: Vec::with_capacity(cap),
+    /// Resolve a name against the folder the dialog is currently showing.
+    ///
+    /// If the dialog has no current folder, the name is resolved on its own,
+    /// which will only succeed for absolute names.
+    pub fn resolve_current(&self, name: &CWideStr) -> Result<ShellItem, HResult> {
+        match self.get_folder()? {
+            Some(folder) => ShellItem::from_relative_name(&folder, name),
+            None => ShellItem::from_parsing_name(name),
         }
     }
 
-    /// Get the number of file filters
-    pub fn len(&self) -> usize {
-        self.filters.len()
-    }
+    /// Set a filter that decides which items are shown.
+    ///
+    /// The closure is called for every item the dialog would show,
+    /// which can be slow for large folders, so it should be cheap.
+    /// Microsoft documents `SetFilter` as unavailable as of Windows 7,
+    /// so newer versions of Windows may ignore the filter or return an error.
+    pub fn set_item_filter<F>(&self, filter: F) -> Result<(), HResult>
+    where
+        F: Fn(&ShellItem) -> bool + 'static,
+    {
+        let filter = shell_item_filter::new(Box::new(filter));
+        let ret = unsafe { self.0.as_ref().SetFilter(filter.as_ptr()) };
+        // The dialog holds its own reference if it needs one.
+        unsafe {
+            filter.as_ref().Release();
+        }
 
-    /// Check if this has file filters in it
-    pub fn is_empty(&self) -> bool {
-        self.filters.is_empty()
-    }
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
 
-    /// Get the inner COMDLG_FILTERSPEC list ptr
-    pub fn as_ptr(&self) -> *const COMDLG_FILTERSPEC {
-        self.filters.as_ptr()
+        Ok(())
     }
 
-    /// Add a filter
-    pub fn add_filter(
-        &mut self,
-        name: impl Into<Cow<'s, CWideStr>>,
-        filter: impl Into<Cow<'s, CWideStr>>,
-    ) {
-        let name = name.into();
-        let filter = filter.into();
-        self.filters.push(COMDLG_FILTERSPEC {
-            pszName: name.as_ptr(),
-            pszSpec: filter.as_ptr(),
-        });
-        self.storage.push((name, filter));
-    }
-}
+    /// Register callbacks for events that occur while the dialog is shown.
+    ///
+    /// Returns a cookie that can be passed to [`FileDialog::unadvise`].
+    /// The dialog keeps the callbacks alive until it is dropped or they are unadvised.
+    pub fn advise<E>(&self, events: E) -> Result<u32, HResult>
+    where
+        E: FileDialogEvents + 'static,
+    {
+        let sink = file_dialog_events::new(Box::new(events));
+        let mut cookie = 0;
+        let ret = unsafe { self.0.as_ref().Advise(sink.as_ptr(), &mut cookie) };
+        // The dialog holds its own reference if it needs one.
+        unsafe {
+            sink.as_ref().Release();
+        }
 
-impl Default for FileFilters<'_> {
-    fn default() -> Self {
-        Self::new()
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(cookie)
     }
-}
 
-extern "system" {
-    fn SHCreateItemFromIDList(
-        pidl: PCIDLIST_ABSOLUTE,
-        riid: REFIID,
-        ppv: *mut *mut c_void,
-    ) -> HRESULT;
-}
+    /// Remove callbacks registered with [`FileDialog::advise`].
+    pub fn unadvise(&self, cookie: u32) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().Unadvise(cookie) };
 
-/// A Shell Item
-#[repr(transparent)]
-pub struct ShellItem(NonNull<IShellItem>);
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
 
-impl ShellItem {
-    /// Try to create a [`ShellItem`] from a path.
-    ///
-    /// This will allocate internally to work with relative paths.
-    ///
-    /// # Panics
-    /// Panics if the path contains interior NULs.
-    ///
-    /// # Errors
-    /// Returns an error if the absolute path could not be acquired or if
-    /// the shell item could not be created.
-    pub fn from_path(path: &Path) -> Result<Self, HResult> {
-        let path = CWideString::new(path).expect("path contains NUL");
-        let (path, _filename_index) = get_full_path_name(&path)?;
-        Self::from_parsing_name(&path)
+        Ok(())
     }
 
-    /// Try to create a [`ShellItem`] from a path.
-    ///
-    /// Note that this does not work with relative paths.
-    pub fn from_parsing_name(path: &CWideStr) -> Result<Self, HResult> {
-        let mut ptr = std::ptr::null_mut();
-        let ret = unsafe {
-            SHCreateItemFromParsingName(
-                path.as_ptr(),
-                std::ptr::null_mut(),
-                &IShellItem::uuidof(),
-                &mut ptr,
-            )
-        };
+    /// Get the 1-based index of the selected file type.
+    pub fn get_filetype_index(&self) -> Result<u32, HResult> {
+        let mut index = 0;
+        let ret = unsafe { self.0.as_ref().GetFileTypeIndex(&mut index) };
 
         if FAILED(ret) {
             return Err(HResult::from(ret));
         }
 
-        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
-
-        Ok(Self(ptr))
+        Ok(index)
     }
 
-    /// Try to create a [`ShellItem`] from an [`ItemIdList`].
-    pub fn from_id_list(list: &ItemIdList) -> Result<Self, HResult> {
-        let mut ptr = std::ptr::null_mut();
-        let ret =
-            unsafe { SHCreateItemFromIDList(*list.as_ptr(), &IShellItem::uuidof(), &mut ptr) };
+    /// Set the 1-based index of the selected file type.
+    pub fn set_filetype_index(&self, index: u32) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().SetFileTypeIndex(index) };
+
         if FAILED(ret) {
             return Err(HResult::from(ret));
         }
-        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
 
-        Ok(Self(ptr))
+        Ok(())
     }
 
-    /// Get the display name of a shell item.
+    /// Set the label of the cancel button.
+    ///
+    /// # Errors
+    /// Returns `E_NOINTERFACE` if `IFileDialog2` is not available, like before Windows 7.
+    pub fn set_cancel_button_label(&self, label: &CWideStr) -> Result<(), HResult> {
+        let dialog = self.file_dialog2()?;
+        let ret = unsafe { dialog.0.as_ref().SetCancelButtonLabel(label.as_ptr()) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Set the root of the navigation pane, preventing the user from browsing outside of it.
+    ///
+    /// # Errors
+    /// Returns `E_NOINTERFACE` if `IFileDialog2` is not available, like before Windows 7.
+    pub fn set_navigation_root(&self, item: ShellItem) -> Result<(), HResult> {
+        let dialog = self.file_dialog2()?;
+        // The dialog holds its own reference if it needs one.
+        let ret = unsafe { dialog.0.as_ref().SetNavigationRoot(item.0.as_ptr()) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Get the `IFileDialog2` interface of this dialog.
+    fn file_dialog2(&self) -> Result<FileDialog2, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            self.0
+                .as_ref()
+                .QueryInterface(&IFileDialog2::uuidof(), &mut ptr)
+        };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(FileDialog2(ptr))
+    }
+
+    /// Get the window of the dialog.
+    ///
+    /// This only succeeds while the dialog is shown, like in a [`FileDialogEvents`] callback.
+    pub fn get_window(&self) -> Result<HWND, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            self.0
+                .as_ref()
+                .QueryInterface(&IOleWindow::uuidof(), &mut ptr)
+        };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let window: NonNull<IOleWindow> = NonNull::new(ptr).expect("ptr is null").cast();
+
+        let mut hwnd = std::ptr::null_mut();
+        let ret = unsafe {
+            let ret = window.as_ref().GetWindow(&mut hwnd);
+            window.as_ref().Release();
+            ret
+        };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(hwnd)
+    }
+
+    /// Add a folder to the places in the navigation pane.
+    ///
+    /// If `top` is true, the place is added above the pinned places instead of below them.
+    pub fn add_place(&self, item: &ShellItem, top: bool) -> Result<(), HResult> {
+        let fdap = if top { FDAP_TOP } else { FDAP_BOTTOM };
+        let ret = unsafe { self.0.as_ref().AddPlace(item.0.as_ptr(), fdap) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Get an interface for adding controls to this dialog.
+    pub fn customize(&self) -> Result<FileDialogCustomize, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            self.0
+                .as_ref()
+                .QueryInterface(&IFileDialogCustomize::uuidof(), &mut ptr)
+        };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(FileDialogCustomize(ptr))
+    }
+
+    /// Get single result.
+    ///
+    /// Use [`FileDialog::try_get_result`] to handle a missing result.
+    pub fn get_result(&self) -> Result<ShellItem, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe { self.0.as_ref().GetResult(&mut ptr) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr was null");
+        Ok(ShellItem(ptr))
+    }
+
+    /// Get single result, if there is one.
+    ///
+    /// Returns `None` if the dialog has no result, like after it was cancelled.
+    pub fn try_get_result(&self) -> Result<Option<ShellItem>, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe { self.0.as_ref().GetResult(&mut ptr) };
+
+        if ret == E_UNEXPECTED || ret == HRESULT_FROM_WIN32(ERROR_CANCELLED) {
+            return Ok(None);
+        }
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr was null");
+        Ok(Some(ShellItem(ptr)))
+    }
+
+    /// Show the window
+    pub fn show(&self, parent: Option<HWND>) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().Show(parent.unwrap_or(std::ptr::null_mut())) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+}
+
+/// View an interface pointer as a wrapper of one of the interfaces it inherits from.
+///
+/// A COM interface's vtable starts with the vtable of the interface it inherits from,
+/// so a pointer to the derived interface is also a valid pointer to the base interface.
+/// Only the reference to the pointer field is reinterpreted, not the wrapper that contains it,
+/// so this stays sound if fields are added to the derived wrapper.
+///
+/// # Safety
+/// * `W` must be a `#[repr(transparent)]` wrapper around a `NonNull` of an interface that `I` inherits from
+unsafe fn upcast_ref<I, W>(ptr: &NonNull<I>) -> &W {
+    &*(ptr as *const NonNull<I> as *const W)
+}
+
+impl Deref for FileDialog {
+    type Target = ModalWindow;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety:
+        // ModalWindow is a transparent wrapper around a `NonNull<IModalWindow>`,
+        // and the interface of FileDialog inherits from `IModalWindow`.
+        unsafe { upcast_ref::<_, ModalWindow>(&self.0) }
+    }
+}
+
+impl Drop for FileDialog {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// An owned `IFileDialog2` pointer
+struct FileDialog2(NonNull<IFileDialog2>);
+
+impl Drop for FileDialog2 {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// A File Open Dialog
+#[repr(transparent)]
+pub struct FileOpenDialog(NonNull<IFileOpenDialog>);
+
+impl FileOpenDialog {
+    /// Make a new [`FileOpenDialog`].
+    pub fn new() -> Result<Self, HResult> {
+        let ptr = unsafe { skylight::create_instance(&CLSID_FileOpenDialog, CLSCTX_ALL)? };
+        let ptr = NonNull::new(ptr).expect("ptr is null");
+        Ok(Self(ptr))
+    }
+
+    /// Make a new [`FileOpenDialog`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IFileOpenDialog` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IFileOpenDialog>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IFileOpenDialog {
+        self.0.as_ptr()
+    }
+
+    /// Get all results.
+    ///
+    /// Use this instead of [`FileDialog::get_result`] if `FOS_ALLOWMULTISELECT` is set.
+    pub fn get_results(&self) -> Result<ShellItemArray, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe { self.0.as_ref().GetResults(&mut ptr) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr was null");
+        Ok(ShellItemArray(ptr))
+    }
+}
+
+impl Deref for FileOpenDialog {
+    type Target = FileDialog;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety:
+        // FileDialog is a transparent wrapper around a `NonNull<IFileDialog>`,
+        // and the interface of FileOpenDialog inherits from `IFileDialog`.
+        unsafe { upcast_ref::<_, FileDialog>(&self.0) }
+    }
+}
+
+impl Drop for FileOpenDialog {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// A File Save Dialog
+#[repr(transparent)]
+pub struct FileSaveDialog(NonNull<IFileSaveDialog>);
+
+impl FileSaveDialog {
+    /// Make a new [`FileSaveDialog`].
+    pub fn new() -> Result<Self, HResult> {
+        let ptr = unsafe { skylight::create_instance(&CLSID_FileSaveDialog, CLSCTX_ALL)? };
+        let ptr = NonNull::new(ptr).expect("ptr is null");
+        Ok(Self(ptr))
+    }
+
+    /// Make a new [`FileSaveDialog`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IFileSaveDialog` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IFileSaveDialog>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IFileSaveDialog {
+        self.0.as_ptr()
+    }
+
+    /// Get a writable stream for the selected item, creating or truncating it.
+    ///
+    /// This lets items without a file system path, like ones provided by cloud storage, be saved.
+    ///
+    /// # Errors
+    /// Returns an error if there is no result.
+    /// Returns [`NfdError::StreamUnsupported`] if the item does not support being written through a stream.
+    pub fn get_result_stream(&self) -> Result<Stream, NfdError> {
+        let item = self.get_result().map_err(NfdError::context("GetResult"))?;
+        item.bind_to_stream(STGM_WRITE | STGM_CREATE)
+            .map_err(|hr| match hr.0 {
+                E_NOINTERFACE | E_NOTIMPL => NfdError::StreamUnsupported(hr),
+                _ => NfdError::HResultContext {
+                    op: "BindToHandler",
+                    hr,
+                },
+            })
+    }
+}
+
+impl Deref for FileSaveDialog {
+    type Target = FileDialog;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety:
+        // FileDialog is a transparent wrapper around a `NonNull<IFileDialog>`,
+        // and the interface of FileSaveDialog inherits from `IFileDialog`.
+        unsafe { upcast_ref::<_, FileDialog>(&self.0) }
+    }
+}
+
+impl Drop for FileSaveDialog {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// An interface for adding controls to a file dialog
+#[repr(transparent)]
+pub struct FileDialogCustomize(NonNull<IFileDialogCustomize>);
+
+impl FileDialogCustomize {
+    /// Make a new [`FileDialogCustomize`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IFileDialogCustomize` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IFileDialogCustomize>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IFileDialogCustomize {
+        self.0.as_ptr()
+    }
+
+    /// Add a text control with the given id.
+    pub fn add_text(&self, id: u32, text: &CWideStr) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().AddText(id, text.as_ptr()) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Add a separator with the given id, to visually divide groups of controls.
+    pub fn add_separator(&self, id: u32) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().AddSeparator(id) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Place the control with the given id outside the group of added controls, so it is more visible.
+    ///
+    /// Only one control can be made prominent.
+    pub fn make_prominent(&self, id: u32) -> Result<(), HResult> {
+        let ret = unsafe { self.0.as_ref().MakeProminent(id) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for FileDialogCustomize {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// File type filter list
+pub struct FileFilters<'s> {
+    filters: Vec<COMDLG_FILTERSPEC>,
+
+    storage: Vec<(Cow<'s, CWideStr>, Cow<'s, CWideStr>)>,
+}
+
+impl<'s> FileFilters<'s> {
+    /// Make an empty list of file type filters
+    pub fn new() -> Self {
+        Self {
+            filters: Vec::new(),
+            storage: Vec::new(),
+        }
+    }
+
+    /// Get the number of file filters
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            filters: Vec::with_capacity(cap),
+            storage: Vec::with_capacity(cap),
+        }
+    }
+
+    /// Get the number of file filters
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Check if this has file filters in it
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Iterate over the filter specs, like `*.txt;*.lbl`.
+    pub(crate) fn specs(&self) -> impl Iterator<Item = &CWideStr> + '_ {
+        self.storage.iter().map(|(_, spec)| &**spec)
+    }
+
+    /// Iterate over the filter names and specs.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&CWideStr, &CWideStr)> + '_ {
+        self.storage.iter().map(|(name, spec)| (&**name, &**spec))
+    }
+
+    /// Make a list of file type filters from names and specs, like `[("Text Files", "*.txt")]`.
+    ///
+    /// The strings are copied, so the list is not tied to them.
+    ///
+    /// # Errors
+    /// Returns an error if a name or spec contains a NUL.
+    pub fn from_pairs(pairs: &[(&str, &str)]) -> Result<FileFilters<'static>, NulError> {
+        let mut filters = FileFilters::with_capacity(pairs.len());
+        for &(name, spec) in pairs {
+            let name = CWideString::new(name)?;
+            let spec = CWideString::new(spec)?;
+            filters.add_filter(Cow::Owned(name), Cow::Owned(spec));
+        }
+
+        Ok(filters)
+    }
+
+    /// Make a list of file type filters that borrows existing `COMDLG_FILTERSPEC`s.
+    ///
+    /// The strings are not copied.
+    /// Prefer [`FileFilters::add_filter`] unless the specs already exist, like in FFI code.
+    ///
+    /// # Safety
+    /// * every `pszName` and `pszSpec` must be non-null and point to a NUL-terminated wide string
+    /// * the strings must never be freed or changed
+    pub unsafe fn from_raw(specs: &'static [COMDLG_FILTERSPEC]) -> Self {
+        let storage = specs
+            .iter()
+            .map(|spec| {
+                (
+                    Cow::Borrowed(CWideStr::from_ptr(spec.pszName)),
+                    Cow::Borrowed(CWideStr::from_ptr(spec.pszSpec)),
+                )
+            })
+            .collect();
+
+        Self {
+            filters: specs.to_vec(),
+            storage,
+        }
+    }
+
+    /// Get the inner COMDLG_FILTERSPEC list ptr
+    pub fn as_ptr(&self) -> *const COMDLG_FILTERSPEC {
+        self.filters.as_ptr()
+    }
+
+    /// Get the inner COMDLG_FILTERSPEC list.
+    ///
+    /// The pointers in each spec are only valid while this is not modified or dropped.
+    pub fn as_slice(&self) -> &[COMDLG_FILTERSPEC] {
+        &self.filters
+    }
+
+    /// Add a filter
+    ///
+    /// An empty name or spec is almost always a mistake, and may cause Windows to ignore the file type dropdown.
+    /// This triggers a debug assertion in that case, but still adds the filter in release builds.
+    /// Use [`FileFilters::try_add_filter`] to handle this case instead.
+    pub fn add_filter(
+        &mut self,
+        name: impl Into<Cow<'s, CWideStr>>,
+        filter: impl Into<Cow<'s, CWideStr>>,
+    ) {
+        let name = name.into();
+        let filter = filter.into();
+        let validation = validate_filter(&name, &filter);
+        if validation.is_err() {
+            debug!("adding invalid filter: {:?}", validation);
+        }
+        debug_assert!(validation.is_ok(), "{:?}", validation);
+
+        self.push_filter(name, filter);
+    }
+
+    /// Try to add a filter.
+    ///
+    /// # Errors
+    /// Returns an error if the name or spec is empty.
+    pub fn try_add_filter(
+        &mut self,
+        name: impl Into<Cow<'s, CWideStr>>,
+        filter: impl Into<Cow<'s, CWideStr>>,
+    ) -> Result<(), FileFilterError> {
+        let name = name.into();
+        let filter = filter.into();
+        validate_filter(&name, &filter)?;
+
+        self.push_filter(name, filter);
+
+        Ok(())
+    }
+
+    fn push_filter(&mut self, name: Cow<'s, CWideStr>, filter: Cow<'s, CWideStr>) {
+        self.filters.push(COMDLG_FILTERSPEC {
+            pszName: name.as_ptr(),
+            pszSpec: filter.as_ptr(),
+        });
+        self.storage.push((name, filter));
+    }
+
+    /// Add a filter for a file extension, like `png`.
+    ///
+    /// If no name is given, one like `PNG Files (*.png)` is generated.
+    pub fn add_extension(&mut self, ext: &str, name: Option<&str>) -> Result<(), NulError> {
+        self.add_extensions(&[ext], name)
+    }
+
+    /// Add a single filter for multiple file extensions, like `png` and `jpg`.
+    ///
+    /// The patterns are combined into one spec, like `*.png;*.jpg`.
+    /// If no name is given, one like `PNG/JPG Files (*.png;*.jpg)` is generated.
+    /// Nothing is added if `exts` is empty, since the filter would have an empty spec.
+    pub fn add_extensions(&mut self, exts: &[&str], name: Option<&str>) -> Result<(), NulError> {
+        if exts.is_empty() {
+            debug!("not adding a filter without extensions");
+            return Ok(());
+        }
+
+        let exts: Vec<&str> = exts.iter().map(|ext| ext.trim_start_matches('.')).collect();
+        let spec = exts
+            .iter()
+            .map(|ext| format!("*.{}", ext))
+            .collect::<Vec<_>>()
+            .join(";");
+        let name = match name {
+            Some(name) => CWideString::new(name)?,
+            None => {
+                let upper = exts
+                    .iter()
+                    .map(|ext| ext.to_uppercase())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                CWideString::new(format!("{} Files ({})", upper, spec).as_str())?
+            }
+        };
+        let spec = CWideString::new(spec.as_str())?;
+        self.add_filter(Cow::Owned(name), Cow::Owned(spec));
+
+        Ok(())
+    }
+
+    /// Add a filter for a file extension, with a friendly name if it is well-known.
+    ///
+    /// For example, `txt` is named `Text Documents (*.txt)`.
+    /// See [`KNOWN_EXTENSIONS`] for the extensions that have friendly names.
+    /// Other extensions fall back to a name like `EXT Files (*.ext)`.
+    pub fn add_known(&mut self, ext: &str) -> Result<(), NulError> {
+        let ext = ext.trim_start_matches('.');
+        let known = KNOWN_EXTENSIONS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(ext));
+
+        match known {
+            Some((_, name)) => {
+                let name = format!("{} (*.{})", name, ext);
+                self.add_extension(ext, Some(&name))
+            }
+            None => self.add_extension(ext, None),
+        }
+    }
+}
+
+/// Well-known file extensions and their friendly names, used by [`FileFilters::add_known`]
+pub const KNOWN_EXTENSIONS: &[(&str, &str)] = &[
+    ("txt", "Text Documents"),
+    ("md", "Markdown Documents"),
+    ("pdf", "PDF Documents"),
+    ("html", "HTML Documents"),
+    ("htm", "HTML Documents"),
+    ("csv", "CSV Files"),
+    ("json", "JSON Files"),
+    ("xml", "XML Files"),
+    ("png", "PNG Images"),
+    ("jpg", "JPEG Images"),
+    ("jpeg", "JPEG Images"),
+    ("gif", "GIF Images"),
+    ("bmp", "Bitmap Images"),
+    ("ico", "Icons"),
+    ("mp3", "MP3 Audio"),
+    ("wav", "WAVE Audio"),
+    ("mp4", "MP4 Videos"),
+    ("zip", "ZIP Archives"),
+    ("exe", "Applications"),
+];
+
+/// Convert the number of filters into the type `SetFileTypes` expects.
+fn filter_count(len: usize) -> Result<u32, HResult> {
+    len.try_into().map_err(|_| HResult::from(E_INVALIDARG))
+}
+
+/// Check that a filter's name and spec are not empty
+fn validate_filter(name: &CWideStr, filter: &CWideStr) -> Result<(), FileFilterError> {
+    if name.as_slice().is_empty() {
+        return Err(FileFilterError::EmptyName);
+    }
+
+    if filter.as_slice().is_empty() {
+        return Err(FileFilterError::EmptySpec);
+    }
+
+    Ok(())
+}
+
+/// An error that may occur while adding a file filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum FileFilterError {
+    /// The filter name was empty
+    #[error("the filter name is empty")]
+    EmptyName,
+
+    /// The filter spec was empty
+    #[error("the filter spec is empty")]
+    EmptySpec,
+}
+
+impl Default for FileFilters<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for FileFilters<'_> {
+    fn clone(&self) -> Self {
+        // The filter specs point into the storage, so they must be rebuilt to point into the clone's storage.
+        let mut filters = Self::with_capacity(self.len());
+        for (name, filter) in self.storage.iter() {
+            filters.push_filter(name.clone(), filter.clone());
+        }
+        filters
+    }
+}
+
+extern "system" {
+    fn SHCreateItemFromIDList(
+        pidl: PCIDLIST_ABSOLUTE,
+        riid: REFIID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+
+    fn SHCreateItemWithParent(
+        pidlParent: PCIDLIST_ABSOLUTE,
+        psfParent: *mut IShellFolder,
+        pidl: PCUITEMID_CHILD,
+        riid: REFIID,
+        ppvItem: *mut *mut c_void,
+    ) -> HRESULT;
+
+    fn SHCreateItemFromRelativeName(
+        psiParent: *mut IShellItem,
+        pszName: PCWSTR,
+        pbc: *mut IBindCtx,
+        riid: REFIID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+
+    fn CreateBindCtx(reserved: DWORD, ppbc: *mut *mut IBindCtx) -> HRESULT;
+
+    fn SHGetKnownFolderItem(
+        rfid: REFKNOWNFOLDERID,
+        flags: DWORD,
+        hToken: HANDLE,
+        riid: REFIID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+}
+
+/// Get a [`ShellItem`] for the root of each logical drive.
+///
+/// Drives that cannot be resolved, like an empty disc drive, are skipped.
+pub fn enumerate_drives() -> Result<Vec<ShellItem>, HResult> {
+    let drives = get_logical_drive_strings()?
+        .iter()
+        .filter_map(|drive| match ShellItem::from_parsing_name(drive) {
+            Ok(item) => Some(item),
+            Err(_e) => {
+                debug!("failed to resolve drive '{:?}': {}", drive, _e);
+                None
+            }
+        })
+        .collect();
+
+    Ok(drives)
+}
+
+/// Replace forward slashes with backslashes in a parsing name, since the shell rejects them in file system paths.
+///
+/// URLs, which contain `://`, are left as-is.
+fn normalize_separators(name: &CWideStr) -> Cow<'_, CWideStr> {
+    let slash = u16::from(b'/');
+    let backslash = u16::from(b'\\');
+    let url_separator = CWideString::new("://").expect("invalid c wide string");
+
+    if !name.contains_unit(slash) || name.contains(&url_separator) {
+        return Cow::Borrowed(name);
+    }
+
+    let normalized: Vec<u16> = name
+        .as_slice()
+        .iter()
+        .map(|&unit| if unit == slash { backslash } else { unit })
+        .collect();
+
+    Cow::Owned(CWideString::new(normalized).expect("CWideStr contained an interior NUL"))
+}
+
+/// BHID_SFObject, used to bind a [`ShellItem`] to an `IShellFolder`.
+const BHID_SF_OBJECT: GUID = GUID {
+    Data1: 0x3981_e224,
+    Data2: 0xf559,
+    Data3: 0x11d3,
+    Data4: [0x8e, 0x3a, 0x00, 0xc0, 0x4f, 0x68, 0x37, 0xd5],
+};
+
+/// BHID_Stream, used to bind a [`ShellItem`] to an `IStream`.
+const BHID_STREAM: GUID = GUID {
+    Data1: 0x1ceb_b3ab,
+    Data2: 0x7c10,
+    Data3: 0x499a,
+    Data4: [0xa4, 0x17, 0x92, 0xca, 0x16, 0xc4, 0xcb, 0x83],
+};
+
+/// BHID_EnumItems, used to bind a folder [`ShellItem`] to an `IEnumShellItems`.
+const BHID_ENUM_ITEMS: GUID = GUID {
+    Data1: 0x94f6_0519,
+    Data2: 0x2850,
+    Data3: 0x4924,
+    Data4: [0xaa, 0x5a, 0xd1, 0x5e, 0x84, 0x86, 0x80, 0x39],
+};
+
+/// The prefix of a verbatim path, `\\?\`
+const VERBATIM_PREFIX: [u16; 4] = [b'\\' as u16, b'\\' as u16, b'?' as u16, b'\\' as u16];
+
+/// The prefix of a verbatim UNC path, `\\?\UNC\`
+const VERBATIM_UNC_PREFIX: [u16; 8] = [
+    b'\\' as u16,
+    b'\\' as u16,
+    b'?' as u16,
+    b'\\' as u16,
+    b'U' as u16,
+    b'N' as u16,
+    b'C' as u16,
+    b'\\' as u16,
+];
+
+/// A Shell Item
+#[repr(transparent)]
+pub struct ShellItem(NonNull<IShellItem>);
+
+impl ShellItem {
+    /// Make a new [`ShellItem`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IShellItem` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IShellItem>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IShellItem {
+        self.0.as_ptr()
+    }
+
+    /// Try to create a [`ShellItem`] from a path.
+    ///
+    /// This will allocate internally to work with relative paths.
+    ///
+    /// # Panics
+    /// Panics if the path contains interior NULs.
+    ///
+    /// # Errors
+    /// Returns an error if the absolute path could not be acquired or if
+    /// the shell item could not be created.
+    pub fn from_path(path: &Path) -> Result<Self, HResult> {
+        let path = CWideString::new(path).expect("path contains NUL");
+        let (path, _filename_index) = get_full_path_name(&path)?;
+        Self::from_parsing_name(&path)
+    }
+
+    /// Make a [`ShellItem`] for the path of an open file, like for `IFileSaveDialog::SetSaveAsItem`.
+    ///
+    /// # Errors
+    /// Returns `E_INVALIDARG` if the file is not on disk, like a pipe or socket, since it has no path.
+    /// Returns an error if the path could not be acquired or if the shell item could not be created.
+    pub fn from_file(file: &File) -> Result<Self, HResult> {
+        let path = unsafe { get_final_path_name_by_handle(file.as_raw_handle().cast())? };
+
+        // The path is a verbatim path, like `\\?\C:\foo`, which the shell may not parse.
+        let path = path.as_slice();
+        let path = if let Some(rest) = path.strip_prefix(&VERBATIM_UNC_PREFIX[..]) {
+            let mut unc = vec![u16::from(b'\\'); 2];
+            unc.extend_from_slice(rest);
+            unc
+        } else {
+            path.strip_prefix(&VERBATIM_PREFIX[..])
+                .unwrap_or(path)
+                .to_vec()
+        };
+        let path = CWideString::new(path).expect("path contains NUL");
+
+        Self::from_parsing_name(&path)
+    }
+
+    /// Check whether a path can be turned into a [`ShellItem`] with [`ShellItem::from_path`].
+    ///
+    /// This is useful to validate a configured folder before passing it to a dialog.
+    ///
+    /// # Panics
+    /// Panics if the path contains interior NULs.
+    pub fn path_is_resolvable(path: &Path) -> bool {
+        match Self::from_path(path) {
+            Ok(_) => true,
+            Err(_e) => {
+                debug!("'{}' is not resolvable: {}", path.display(), _e);
+                false
+            }
+        }
+    }
+
+    /// Try to create a [`ShellItem`] from a path.
+    ///
+    /// Note that this does not work with relative paths.
+    /// Forward slashes are converted to backslashes, unless the name is a URL like `https://example.com/`.
+    pub fn from_parsing_name(path: &CWideStr) -> Result<Self, HResult> {
+        Self::from_parsing_name_with_flags(path, ParseFlags::default())
+    }
+
+    /// Try to create a [`ShellItem`] from a path, resolving it with the given flags.
+    ///
+    /// See [`ShellItem::from_parsing_name`].
+    pub fn from_parsing_name_with_flags(
+        path: &CWideStr,
+        flags: ParseFlags,
+    ) -> Result<Self, HResult> {
+        let path = normalize_separators(path);
+        let bind_ctx = flags.bind_ctx()?;
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            SHCreateItemFromParsingName(
+                path.as_ptr(),
+                bind_ctx
+                    .as_ref()
+                    .map_or(std::ptr::null_mut(), |bind_ctx| bind_ctx.0.as_ptr()),
+                &IShellItem::uuidof(),
+                &mut ptr,
+            )
+        };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(Self(ptr))
+    }
+
+    /// Try to create a [`ShellItem`] from an [`ItemIdList`].
+    pub fn from_id_list(list: &ItemIdList) -> Result<Self, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret =
+            unsafe { SHCreateItemFromIDList(*list.as_ptr(), &IShellItem::uuidof(), &mut ptr) };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(Self(ptr))
+    }
+
+    /// Try to create a [`ShellItem`] for a common known folder.
+    pub fn from_known_folder(folder: KnownFolder) -> Result<Self, HResult> {
+        Self::from_known_folder_id(folder.id())
+    }
+
+    /// Try to create a [`ShellItem`] for any known folder, like `FOLDERID_Documents`.
+    ///
+    /// Prefer [`ShellItem::from_known_folder`] for common folders.
+    pub fn from_known_folder_id(id: &KNOWNFOLDERID) -> Result<Self, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            SHGetKnownFolderItem(id, 0, std::ptr::null_mut(), &IShellItem::uuidof(), &mut ptr)
+        };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(Self(ptr))
+    }
+
+    /// Try to create a [`ShellItem`] from a name relative to a parent [`ShellItem`].
+    pub fn from_relative_name(parent: &ShellItem, name: &CWideStr) -> Result<Self, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            SHCreateItemFromRelativeName(
+                parent.0.as_ptr(),
+                name.as_ptr(),
+                std::ptr::null_mut(),
+                &IShellItem::uuidof(),
+                &mut ptr,
+            )
+        };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(Self(ptr))
+    }
+
+    /// Bind this item to a [`ShellFolder`].
+    ///
+    /// This will fail if this item is not a folder.
+    pub fn bind_to_storage(&self) -> Result<ShellFolder, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            self.0.as_ref().BindToHandler(
+                std::ptr::null_mut(),
+                &BHID_SF_OBJECT,
+                &IShellFolder::uuidof(),
+                &mut ptr,
+            )
+        };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(ShellFolder(ptr))
+    }
+
+    /// Bind this item to a [`Stream`], opened with the given `STGM_*` access mode.
+    pub fn bind_to_stream(&self, mode: DWORD) -> Result<Stream, HResult> {
+        let bind_ctx = BindCtx::new()?;
+        bind_ctx.set_mode(mode)?;
+
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            self.0.as_ref().BindToHandler(
+                bind_ctx.0.as_ptr(),
+                &BHID_STREAM,
+                &IStream::uuidof(),
+                &mut ptr,
+            )
+        };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(Stream(ptr))
+    }
+
+    /// Get the display name of a shell item.
     pub fn get_display_name(
         &self,
         display_type: DisplayNameType,
     ) -> Result<CoTaskMemWideString, HResult> {
         let display_type: SIGDN = display_type.into();
         let mut ptr = std::ptr::null_mut();
-        let ret = unsafe { self.0.as_ref().GetDisplayName(display_type, &mut ptr) };
+        let ret = unsafe { self.0.as_ref().GetDisplayName(display_type, &mut ptr) };
+
+        if FAILED(ret) {
+            Err(HResult::from(ret))
+        } else {
+            let ptr = NonNull::new(ptr).expect("ptr was null");
+            Ok(unsafe { CoTaskMemWideString::from_raw(ptr) })
+        }
+    }
+
+    /// Get the name of this item as it should be shown to the user, like `Cargo.toml` or `Documents`.
+    ///
+    /// This is the [`DisplayNameType::NormalDisplay`] form.
+    /// Invalid UTF-16 is replaced with the replacement character.
+    pub fn name(&self) -> Result<String, HResult> {
+        let name = self.get_display_name(DisplayNameType::NormalDisplay)?;
+        Ok(name.as_os_string().to_string_lossy().into_owned())
+    }
+
+    /// Get the URL of this item, or `None` if it does not have one.
+    ///
+    /// Files and folders have `file:` URLs, and items from web or cloud storage locations may have `http:` URLs.
+    /// Virtual items, like the control panel, usually do not have a URL.
+    /// Invalid UTF-16 is replaced with the replacement character.
+    pub fn url(&self) -> Result<Option<String>, HResult> {
+        match self.get_display_name(DisplayNameType::Url) {
+            Ok(url) => Ok(Some(url.as_os_string().to_string_lossy().into_owned())),
+            // The shell reports that an item has no URL as an unsupported display name type.
+            Err(e) if e.0 == E_INVALIDARG || e.0 == E_NOTIMPL => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Iterate over the immediate children of this folder item.
+    ///
+    /// Children are fetched one at a time as the iterator advances.
+    /// The iterator ends after yielding an error.
+    ///
+    /// # Errors
+    /// Returns an error if this item is not a folder.
+    pub fn children(&self) -> Result<impl Iterator<Item = Result<ShellItem, HResult>>, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            self.0.as_ref().BindToHandler(
+                std::ptr::null_mut(),
+                &BHID_ENUM_ITEMS,
+                &IEnumShellItems::uuidof(),
+                &mut ptr,
+            )
+        };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(EnumShellItems { ptr, done: false })
+    }
+
+    /// Serialize the item id list (PIDL) of this item, to restore it later with [`ShellItem::from_pidl_bytes`].
+    ///
+    /// Unlike a path, this keeps referring to virtual items, like libraries and phones.
+    /// However, the format of PIDLs is private to the folders that make them,
+    /// so the bytes may fail to resolve on another machine, after a Windows update, or after the item is moved or deleted.
+    pub fn to_pidl_bytes(&self) -> Result<Vec<u8>, HResult> {
+        let mut pidl = std::ptr::null_mut();
+        let ret = unsafe { SHGetIDListFromObject(self.0.as_ptr().cast(), &mut pidl) };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        assert!(!pidl.is_null(), "pidl is null");
+        let list = ItemIdList(pidl);
+
+        Ok(list.as_bytes().to_vec())
+    }
+
+    /// Restore an item from bytes made by [`ShellItem::to_pidl_bytes`].
+    ///
+    /// # Errors
+    /// Returns `E_INVALIDARG` if the bytes are not a complete item id list.
+    /// Returns an error if the item could not be resolved, like if it no longer exists.
+    pub fn from_pidl_bytes(bytes: &[u8]) -> Result<Self, HResult> {
+        if !is_valid_id_list(bytes) {
+            return Err(HResult::from(E_INVALIDARG));
+        }
+
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            SHCreateItemFromIDList(bytes.as_ptr().cast(), &IShellItem::uuidof(), &mut ptr)
+        };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+
+        Ok(Self(ptr))
+    }
+
+    /// Get the most useful display name this item supports.
+    ///
+    /// This tries, in order:
+    /// 1. [`DisplayNameType::FileSysPath`], like `C:\Users\user\Documents\notes.txt`
+    /// 2. [`DisplayNameType::Url`], like `https://example.com/notes.txt`
+    /// 3. [`DisplayNameType::DesktopAbsoluteParsing`], like `::{20D04FE0-3AEA-1069-A2D8-08002B30309D}`
+    /// 4. [`DisplayNameType::NormalDisplay`], like `This PC`
+    ///
+    /// and returns the first that succeeds.
+    /// If all of them fail, the error of the last one is returned.
+    pub fn best_display_name(&self) -> Result<String, HResult> {
+        const ORDER: [DisplayNameType; 4] = [
+            DisplayNameType::FileSysPath,
+            DisplayNameType::Url,
+            DisplayNameType::DesktopAbsoluteParsing,
+            DisplayNameType::NormalDisplay,
+        ];
+
+        let mut error = None;
+        for &display_type in ORDER.iter() {
+            match self.get_display_name(display_type) {
+                Ok(name) => return Ok(name.as_os_string().to_string_lossy().into_owned()),
+                Err(e) => {
+                    debug!("GetDisplayName({:?}) failed: {}", display_type, e);
+                    error = Some(e);
+                }
+            }
+        }
+
+        Err(error.expect("no display name types were tried"))
+    }
+}
+
+impl Drop for ShellItem {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+impl std::fmt::Debug for ShellItem {
+    /// Print the normal display name, or the pointer if the name could not be acquired.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.get_display_name(DisplayNameType::NormalDisplay) {
+            Ok(name) => f
+                .debug_tuple("ShellItem")
+                .field(&name.as_os_string())
+                .finish(),
+            Err(_e) => f.debug_tuple("ShellItem").field(&self.0).finish(),
+        }
+    }
+}
+
+/// An iterator over the children of a [`ShellItem`], from [`ShellItem::children`]
+struct EnumShellItems {
+    ptr: NonNull<IEnumShellItems>,
+    done: bool,
+}
+
+impl Iterator for EnumShellItems {
+    type Item = Result<ShellItem, HResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut item = std::ptr::null_mut();
+        let mut fetched = 0;
+        let ret = unsafe { self.ptr.as_ref().Next(1, &mut item, &mut fetched) };
+        if FAILED(ret) {
+            self.done = true;
+            return Some(Err(HResult::from(ret)));
+        }
+
+        // S_FALSE means that fewer items than requested were left.
+        if ret == S_FALSE || fetched == 0 {
+            self.done = true;
+            return None;
+        }
+
+        Some(Ok(ShellItem(NonNull::new(item).expect("ptr is null"))))
+    }
+}
+
+impl Drop for EnumShellItems {
+    fn drop(&mut self) {
+        unsafe {
+            self.ptr.as_ref().Release();
+        }
+    }
+}
+
+/// A Shell Item Array
+#[repr(transparent)]
+pub struct ShellItemArray(NonNull<IShellItemArray>);
+
+impl ShellItemArray {
+    /// Make a new [`ShellItemArray`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IShellItemArray` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IShellItemArray>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IShellItemArray {
+        self.0.as_ptr()
+    }
+
+    /// Get the number of items.
+    pub fn get_count(&self) -> Result<u32, HResult> {
+        let mut count = 0;
+        let ret = unsafe { self.0.as_ref().GetCount(&mut count) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(count)
+    }
+
+    /// Get the item at the given index.
+    pub fn get_item_at(&self, index: u32) -> Result<ShellItem, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe { self.0.as_ref().GetItemAt(index, &mut ptr) };
+
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr was null");
+        Ok(ShellItem(ptr))
+    }
+
+    /// Iterate over the items.
+    ///
+    /// The number of items is fetched once, up front.
+    pub fn iter(&self) -> Result<ShellItemArrayIter<'_>, HResult> {
+        Ok(ShellItemArrayIter {
+            array: self,
+            index: 0,
+            count: self.get_count()?,
+            error: None,
+        })
+    }
+
+    /// Lazily get the file system path of each item.
+    ///
+    /// Each path is resolved as the iterator advances, so callers can stop early without resolving the rest.
+    pub fn paths(&self) -> impl Iterator<Item = Result<PathBuf, HResult>> + '_ {
+        self.into_iter().map(|item| {
+            let path = item?.get_display_name(DisplayNameType::FileSysPath)?;
+            Ok(PathBuf::from(path.as_os_string()))
+        })
+    }
+
+    /// Get the file system paths of the items that match a predicate.
+    ///
+    /// Items without a file system path, like virtual folders, are skipped without calling the predicate.
+    ///
+    /// # Errors
+    /// Returns an error if an item can not be fetched, or its path fails to resolve for another reason.
+    pub fn to_paths_filtered<F>(&self, pred: F) -> Result<Vec<PathBuf>, HResult>
+    where
+        F: Fn(&Path) -> bool,
+    {
+        let mut paths = Vec::new();
+        for item in self.iter()? {
+            let path = match item?.get_display_name(DisplayNameType::FileSysPath) {
+                Ok(path) => PathBuf::from(path.as_os_string()),
+                // These mean the item has no file system path.
+                Err(e) if e.0 == E_INVALIDARG || e.0 == E_NOTIMPL => continue,
+                Err(e) => return Err(e),
+            };
+
+            if pred(&path) {
+                paths.push(path);
+            }
+        }
+
+        Ok(paths)
+    }
+}
+
+impl<'a> IntoIterator for &'a ShellItemArray {
+    type Item = Result<ShellItem, HResult>;
+    type IntoIter = ShellItemArrayIter<'a>;
+
+    /// Iterate over the items.
+    ///
+    /// If the number of items cannot be fetched, the error is yielded once and iteration stops.
+    fn into_iter(self) -> Self::IntoIter {
+        let (count, error) = match self.get_count() {
+            Ok(count) => (count, None),
+            Err(error) => (0, Some(error)),
+        };
+
+        ShellItemArrayIter {
+            array: self,
+            index: 0,
+            count,
+            error,
+        }
+    }
+}
+
+impl Drop for ShellItemArray {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// An iterator over the items of a [`ShellItemArray`]
+pub struct ShellItemArrayIter<'a> {
+    array: &'a ShellItemArray,
+    index: u32,
+    count: u32,
+    error: Option<HResult>,
+}
+
+impl Iterator for ShellItemArrayIter<'_> {
+    type Item = Result<ShellItem, HResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        if self.index >= self.count {
+            return None;
+        }
+
+        let item = self.array.get_item_at(self.index);
+        self.index += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.count - self.index) as usize + usize::from(self.error.is_some());
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for ShellItemArrayIter<'_> {}
+
+/// A Shell Folder
+#[repr(transparent)]
+pub struct ShellFolder(NonNull<IShellFolder>);
+
+impl ShellFolder {
+    /// Make a new [`ShellFolder`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IShellFolder` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IShellFolder>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IShellFolder {
+        self.0.as_ptr()
+    }
+
+    /// Get the folders and non-folders in this folder.
+    ///
+    /// Hidden items are not included.
+    pub fn children(&self) -> Result<Vec<ShellItem>, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            self.0.as_ref().EnumObjects(
+                std::ptr::null_mut(),
+                SHCONTF_FOLDERS | SHCONTF_NONFOLDERS,
+                &mut ptr,
+            )
+        };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        // The enumerator may be null with S_FALSE if there are no children.
+        let enum_id_list = match NonNull::new(ptr) {
+            Some(ptr) => EnumIdList(ptr),
+            None => return Ok(Vec::new()),
+        };
+
+        let mut children = Vec::new();
+        loop {
+            let mut child = std::ptr::null_mut();
+            let mut fetched = 0;
+            let ret = unsafe { enum_id_list.0.as_ref().Next(1, &mut child, &mut fetched) };
+            if FAILED(ret) {
+                return Err(HResult::from(ret));
+            }
+            if ret != S_OK || fetched == 0 {
+                break;
+            }
+
+            let mut ptr = std::ptr::null_mut();
+            let ret = unsafe {
+                let ret = SHCreateItemWithParent(
+                    std::ptr::null(),
+                    self.0.as_ptr(),
+                    child,
+                    &IShellItem::uuidof(),
+                    &mut ptr,
+                );
+                CoTaskMemFree(child.cast());
+                ret
+            };
+            if FAILED(ret) {
+                return Err(HResult::from(ret));
+            }
+            let ptr = NonNull::new(ptr).expect("ptr is null").cast();
+            children.push(ShellItem(ptr));
+        }
+
+        Ok(children)
+    }
+}
+
+impl Drop for ShellFolder {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// An owned `IEnumIDList`
+struct EnumIdList(NonNull<IEnumIDList>);
+
+impl Drop for EnumIdList {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// Convert a failed `IStream` call into an [`std::io::Error`] that keeps the [`HResult`].
+fn stream_io_error(ret: HRESULT) -> std::io::Error {
+    let hr = HResult::from(ret);
+    std::io::Error::new(hresult_io_error_kind(&hr), hr)
+}
+
+/// A stream of bytes, like the contents of a file
+#[repr(transparent)]
+pub struct Stream(NonNull<IStream>);
+
+impl Stream {
+    /// Make a new [`Stream`] from a raw interface pointer.
+    ///
+    /// # Safety
+    /// * ptr must be a valid `IStream` pointer
+    /// * ownership of one reference is transferred to the returned wrapper, which will `Release` it on drop.
+    ///   If the caller wants to keep using the pointer, it must `AddRef` it first.
+    pub unsafe fn from_raw(ptr: NonNull<IStream>) -> Self {
+        Self(ptr)
+    }
+
+    /// Get the raw interface pointer.
+    ///
+    /// This is an escape hatch for calling methods that are not wrapped by this crate.
+    /// The pointer is borrowed; the caller must not `Release` it or use it after this wrapper is dropped.
+    pub fn as_raw(&self) -> *mut IStream {
+        self.0.as_ptr()
+    }
+}
+
+impl std::io::Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len: ULONG = buf.len().try_into().unwrap_or(ULONG::MAX);
+        let mut read = 0;
+        let ret = unsafe {
+            self.0
+                .as_ref()
+                .Read(buf.as_mut_ptr().cast(), len, &mut read)
+        };
+        if FAILED(ret) {
+            return Err(stream_io_error(ret));
+        }
+
+        Ok(read as usize)
+    }
+}
+
+impl std::io::Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len: ULONG = buf.len().try_into().unwrap_or(ULONG::MAX);
+        let mut written = 0;
+        let ret = unsafe {
+            self.0
+                .as_ref()
+                .Write(buf.as_ptr().cast(), len, &mut written)
+        };
+        if FAILED(ret) {
+            return Err(stream_io_error(ret));
+        }
+
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let ret = unsafe { self.0.as_ref().Commit(STGC_DEFAULT) };
+        if FAILED(ret) {
+            return Err(stream_io_error(ret));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// Options for resolving a parsing name, passed to the shell through a bind context
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ParseFlags {
+    /// Resolve items that are both files and folders, like zip files, as folders.
+    ///
+    /// This is `STR_PARSE_PREFER_FOLDER_BROWSING`.
+    pub prefer_folder_browsing: bool,
+
+    /// Do not resolve relative names against the desktop.
+    ///
+    /// This is `STR_DONT_PARSE_RELATIVE`.
+    pub dont_parse_relative: bool,
+
+    /// Resolve aliases, like the path of a known folder, to their canonical items.
+    ///
+    /// This is `STR_PARSE_TRANSLATE_ALIASES`.
+    pub translate_aliases: bool,
+}
+
+impl ParseFlags {
+    /// Make a bind context holding these flags, or `None` if no flags are set.
+    fn bind_ctx(&self) -> Result<Option<BindCtx>, HResult> {
+        let params = [
+            (self.prefer_folder_browsing, "Parse Prefer Folder Browsing"),
+            (self.dont_parse_relative, "Don't Parse Relative"),
+            (self.translate_aliases, "Parse Translate Aliases"),
+        ];
+        if params.iter().all(|&(set, _)| !set) {
+            return Ok(None);
+        }
+
+        let bind_ctx = BindCtx::new()?;
+        for &(_, key) in params.iter().filter(|&&(set, _)| set) {
+            bind_ctx.register_flag(key)?;
+        }
+
+        Ok(Some(bind_ctx))
+    }
+}
+
+/// An owned `IBindCtx`
+struct BindCtx(NonNull<IBindCtx>);
+
+impl BindCtx {
+    /// Make a new, empty bind context.
+    fn new() -> Result<Self, HResult> {
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe { CreateBindCtx(0, &mut ptr) };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+        let ptr = NonNull::new(ptr).expect("ptr is null");
+
+        Ok(Self(ptr))
+    }
+
+    /// Set the `STGM_*` access mode used when binding.
+    fn set_mode(&self, mode: DWORD) -> Result<(), HResult> {
+        let mut options = BIND_OPTS {
+            cbStruct: std::mem::size_of::<BIND_OPTS>() as DWORD,
+            grfFlags: 0,
+            grfMode: mode,
+            dwTickCountDeadline: 0,
+        };
+        let ret = unsafe { self.0.as_ref().SetBindOptions(&mut options) };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Register a parameter that the shell only checks for the presence of.
+    fn register_flag(&self, key: &str) -> Result<(), HResult> {
+        let key = CWideString::new(key).expect("key contains NUL");
+
+        // The shell only checks that the key is present, so any object will do as the value.
+        let value = BindCtx::new()?;
+        let ret = unsafe {
+            self.0
+                .as_ref()
+                .RegisterObjectParam(key.as_ptr() as *mut u16, value.0.as_ptr().cast())
+        };
+        if FAILED(ret) {
+            return Err(HResult::from(ret));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for BindCtx {
+    fn drop(&mut self) {
+        unsafe {
+            self.0.as_ref().Release();
+        }
+    }
+}
+
+/// A common known folder
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum KnownFolder {
+    /// The user's desktop
+    Desktop,
+
+    /// The user's documents
+    Documents,
+
+    /// The user's downloads
+    Downloads,
+
+    /// The user's music
+    Music,
+
+    /// The user's pictures
+    Pictures,
+
+    /// The user's videos
+    Videos,
 
-        if FAILED(ret) {
-            Err(HResult::from(ret))
-        } else {
-            let ptr = NonNull::new(ptr).expect("ptr was null");
-            Ok(unsafe { CoTaskMemWideString::from_raw(ptr) })
-        }
-    }
+    /// The user's profile folder, like `C:\Users\name`
+    Profile,
 }
 
-impl Drop for ShellItem {
-    fn drop(&mut self) {
-        unsafe {
-            self.0.as_ref().Release();
+impl KnownFolder {
+    /// Get the id of this known folder.
+    pub fn id(self) -> &'static KNOWNFOLDERID {
+        match self {
+            Self::Desktop => &FOLDERID_Desktop,
+            Self::Documents => &FOLDERID_Documents,
+            Self::Downloads => &FOLDERID_Downloads,
+            Self::Music => &FOLDERID_Music,
+            Self::Pictures => &FOLDERID_Pictures,
+            Self::Videos => &FOLDERID_Videos,
+            Self::Profile => &FOLDERID_Profile,
         }
     }
 }
@@ -457,9 +2204,53 @@ impl From<DisplayNameType> for SIGDN {
     }
 }
 
+impl TryFrom<SIGDN> for DisplayNameType {
+    type Error = UnknownDisplayNameType;
+
+    fn try_from(sigdn: SIGDN) -> Result<Self, Self::Error> {
+        match sigdn {
+            SIGDN_NORMALDISPLAY => Ok(DisplayNameType::NormalDisplay),
+            SIGDN_PARENTRELATIVEPARSING => Ok(DisplayNameType::ParentRelativeParsing),
+            SIGDN_DESKTOPABSOLUTEPARSING => Ok(DisplayNameType::DesktopAbsoluteParsing),
+            SIGDN_PARENTRELATIVEEDITING => Ok(DisplayNameType::ParentRelativeEditing),
+            SIGDN_DESKTOPABSOLUTEEDITING => Ok(DisplayNameType::DesktopAbsoluteEditing),
+            SIGDN_FILESYSPATH => Ok(DisplayNameType::FileSysPath),
+            SIGDN_URL => Ok(DisplayNameType::Url),
+            SIGDN_PARENTRELATIVEFORADDRESSBAR => Ok(DisplayNameType::ParentRelativeForAddressBar),
+            SIGDN_PARENTRELATIVE => Ok(DisplayNameType::ParentRelative),
+            SIGDN_PARENTRELATIVEFORUI => Ok(DisplayNameType::ParentRelativeForUi),
+            sigdn => Err(UnknownDisplayNameType(sigdn)),
+        }
+    }
+}
+
+/// An error that occurs when converting an unknown `SIGDN` into a [`DisplayNameType`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+#[error("unknown SIGDN value 0x{0:08X}")]
+pub struct UnknownDisplayNameType(pub SIGDN);
+
 extern "system" {
     fn ILCreateFromPathW(pszPath: PCWSTR) -> PIDLIST_ABSOLUTE;
     fn ILFree(pidl: PIDLIST_RELATIVE);
+    fn ILGetSize(pidl: PCIDLIST_ABSOLUTE) -> UINT;
+    fn SHGetIDListFromObject(punk: *mut IUnknown, ppidl: *mut PIDLIST_ABSOLUTE) -> HRESULT;
+}
+
+/// Check that bytes hold a complete item id list: a run of `SHITEMID`s, each starting with its `u16` size, ending with a zero size.
+fn is_valid_id_list(bytes: &[u8]) -> bool {
+    let mut offset = 0;
+    loop {
+        let size = match bytes.get(offset..offset + 2) {
+            Some(size) => usize::from(u16::from_le_bytes([size[0], size[1]])),
+            None => return false,
+        };
+
+        match size {
+            0 => return offset + 2 == bytes.len(),
+            1 => return false,
+            _ => offset += size,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -469,16 +2260,19 @@ pub struct ItemIdList(PIDLIST_ABSOLUTE);
 impl ItemIdList {
     /// Create an [`ItemIdList`] from a path.
     ///
+    /// # Errors
+    /// Returns the last Win32 error if the path is rejected.
+    /// Use [`Win32Error::last_error_code`] to get the raw code, or convert it into an [`HResult`] with `?`.
+    ///
     /// # Notes
     /// Alright this function's documentation is horrible, so please PLEASE send a PR if anything looks bad.
     /// This function appears(?) to return NULL if the path is rejected.
-    /// I'm *fairly* certain I can get the last error for more info as well.
-    /// I also know for a fact that this function rejects relative paths with a last error of 1008,
-    /// but I'm not sure why.
-    pub fn create_from_path(data: &CWideStr) -> Result<Self, HResult> {
+    /// It rejects relative paths, usually with a last error of 1008 (`ERROR_NO_TOKEN`),
+    /// but sometimes with 87 (`ERROR_INVALID_PARAMETER`), and I'm not sure why.
+    pub fn create_from_path(data: &CWideStr) -> Result<Self, Win32Error> {
         let ret = unsafe { ILCreateFromPathW(data.as_ptr()) };
         if ret.is_null() {
-            return Err(HResult::get_last_error());
+            return Err(Win32Error::last_error());
         }
         Ok(Self(ret))
     }
@@ -487,6 +2281,14 @@ impl ItemIdList {
     pub fn as_ptr(&self) -> *const PIDLIST_ABSOLUTE {
         &self.0
     }
+
+    /// Get the bytes of the list, including the terminator.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            let size = ILGetSize(self.0);
+            std::slice::from_raw_parts(self.0 as *const u8, size as usize)
+        }
+    }
 }
 
 impl Drop for ItemIdList {
@@ -498,6 +2300,15 @@ impl Drop for ItemIdList {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::CoTaskMemWideStringExt;
+
+    extern "system" {
+        fn SHCreateShellItemArrayFromIDLists(
+            cidl: UINT,
+            rgpidl: *const PCIDLIST_ABSOLUTE,
+            ppsiItemArray: *mut *mut IShellItemArray,
+        ) -> HRESULT;
+    }
 
     #[test]
     fn shell_item_from_parsing_name() {
@@ -519,11 +2330,15 @@ mod test {
     fn bad_id_list_creation() {
         // This rejects relative paths
         let rel_path = CWideString::new("./Cargo.toml").expect("invalid c wide string");
-        let _id_list = ItemIdList::create_from_path(&rel_path).unwrap_err();
+        let error = ItemIdList::create_from_path(&rel_path).unwrap_err();
 
         // I don't know why it does this, but im creating a test to remember that it does this.
-        // assert_eq!(id_list.0, 1008);
-        // And sometimes it isnt? On CI it is 87.
+        // It is usually 1008, but on CI it is 87.
+        assert!(
+            matches!(error.last_error_code(), 1008 | 87),
+            "unexpected error: {}",
+            error
+        );
     }
 
     #[test]
@@ -542,4 +2357,730 @@ mod test {
             .expect("failed to get path");
         dbg!(path);
     }
+
+    #[test]
+    fn resolve_current_folder() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+        let folder = ShellItem::from_path(".".as_ref()).expect("failed to make shell item");
+        dialog.set_folder(folder).expect("failed to set folder");
+
+        let name = CWideString::new("Cargo.toml").expect("invalid c wide string");
+        let item = dialog
+            .resolve_current(&name)
+            .expect("failed to resolve name");
+        let path = item
+            .get_display_name(DisplayNameType::FileSysPath)
+            .expect("failed to get path");
+        assert!(std::path::Path::new(&path.as_os_string()).ends_with("Cargo.toml"));
+    }
+
+    #[test]
+    fn shell_item_array_into_iter() {
+        let array = make_shell_item_array(&["./Cargo.toml", "./src"]);
+
+        let mut names = Vec::new();
+        for item in &array {
+            let path = item
+                .expect("failed to get item")
+                .get_display_name(DisplayNameType::FileSysPath)
+                .expect("failed to get path");
+            names.push(
+                Path::new(&path.as_os_string())
+                    .file_name()
+                    .expect("missing file name")
+                    .to_os_string(),
+            );
+        }
+        assert_eq!(names, ["Cargo.toml", "src"]);
+    }
+
+    #[test]
+    fn shell_item_array_to_paths_filtered() {
+        let array = make_shell_item_array(&["./Cargo.toml", "./src", "./src/lib.rs"]);
+
+        let paths = array
+            .to_paths_filtered(|path| path.extension() == Some("rs".as_ref()))
+            .expect("failed to get paths");
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("lib.rs"));
+    }
+
+    #[test]
+    fn shell_item_array_paths() {
+        let array = make_shell_item_array(&["./Cargo.toml", "./src"]);
+
+        let mut paths = array.paths();
+        let path = paths
+            .next()
+            .expect("missing path")
+            .expect("failed to get path");
+        assert!(path.ends_with("Cargo.toml"));
+        let path = paths
+            .next()
+            .expect("missing path")
+            .expect("failed to get path");
+        assert!(path.ends_with("src"));
+        assert!(paths.next().is_none());
+    }
+
+    /// Make a [`ShellItemArray`] from relative paths.
+    fn make_shell_item_array(paths: &[&str]) -> ShellItemArray {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let id_lists: Vec<ItemIdList> = paths
+            .iter()
+            .map(|path| {
+                let path = CWideString::new(*path).expect("invalid c wide string");
+                let (abs_path, _) =
+                    get_full_path_name(&path).expect("failed to get full path name");
+                ItemIdList::create_from_path(&abs_path).expect("failed to create id list")
+            })
+            .collect();
+        let pidls: Vec<PCIDLIST_ABSOLUTE> = id_lists.iter().map(|id_list| id_list.0).collect();
+
+        let mut ptr = std::ptr::null_mut();
+        let ret = unsafe {
+            SHCreateShellItemArrayFromIDLists(pidls.len() as UINT, pidls.as_ptr(), &mut ptr)
+        };
+        assert!(!FAILED(ret), "failed to create shell item array");
+        ShellItemArray(NonNull::new(ptr).expect("ptr was null"))
+    }
+
+    #[test]
+    fn display_name_as_c_wide_str() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let item = ShellItem::from_path("Cargo.toml".as_ref()).expect("failed to make shell item");
+        let path = item
+            .get_display_name(DisplayNameType::FileSysPath)
+            .expect("failed to get path");
+
+        let needle = CWideString::new("Cargo.toml").expect("invalid c wide string");
+        let path = path.as_c_wide_str();
+        assert!(path.contains(&needle));
+        assert_eq!(path.as_slice_with_nul().last(), Some(&0));
+    }
+
+    #[test]
+    fn normalize_separators() {
+        let normalize = |name: &str| {
+            let name = CWideString::new(name).expect("invalid c wide string");
+            String::from_utf16_lossy(super::normalize_separators(&name).as_slice())
+        };
+
+        assert_eq!(normalize("C:/foo/bar"), "C:\\foo\\bar");
+        assert_eq!(normalize("C:\\foo/bar"), "C:\\foo\\bar");
+        assert_eq!(normalize("C:\\foo\\bar"), "C:\\foo\\bar");
+        assert_eq!(
+            normalize("https://example.com/foo"),
+            "https://example.com/foo"
+        );
+    }
+
+    #[test]
+    fn shell_item_from_forward_slash_parsing_name() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let rel_path = CWideString::new("./Cargo.toml").expect("invalid c wide string");
+        let (abs_path, _) = get_full_path_name(&rel_path).expect("failed to get full path name");
+        let abs_path = String::from_utf16_lossy(abs_path.as_slice()).replace('\\', "/");
+        let abs_path = CWideString::new(abs_path.as_str()).expect("invalid c wide string");
+
+        let item = ShellItem::from_parsing_name(&abs_path).expect("failed to make shell item");
+        let path = item
+            .get_display_name(DisplayNameType::FileSysPath)
+            .expect("failed to get path");
+        assert!(Path::new(&path.as_os_string()).ends_with("Cargo.toml"));
+    }
+
+    #[test]
+    fn file_dialog2_methods() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+
+        let title = CWideString::new("Pick a file").expect("invalid c wide string");
+        dialog.set_title(&title).expect("failed to set title");
+
+        let label = CWideString::new("Pick").expect("invalid c wide string");
+        dialog
+            .set_ok_button_label(&label)
+            .expect("failed to set ok button label");
+
+        let label = CWideString::new("Back").expect("invalid c wide string");
+        dialog
+            .set_cancel_button_label(&label)
+            .expect("failed to set cancel button label");
+
+        let root = ShellItem::from_path(".".as_ref()).expect("failed to make shell item");
+        dialog
+            .set_navigation_root(root)
+            .expect("failed to set navigation root");
+    }
+
+    #[test]
+    fn shell_item_from_known_folder() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let item = ShellItem::from_known_folder(KnownFolder::Documents)
+            .expect("failed to make shell item");
+        let path = item
+            .get_display_name(DisplayNameType::FileSysPath)
+            .expect("failed to get path");
+        assert!(Path::new(&path.as_os_string()).is_absolute());
+    }
+
+    #[test]
+    fn deref_to_base_interfaces() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileSaveDialog::new().expect("failed to create dialog");
+
+        let file_dialog: &FileDialog = &dialog;
+        let modal_window: &ModalWindow = &dialog;
+        assert_eq!(dialog.as_raw() as usize, file_dialog.as_raw() as usize);
+        assert_eq!(dialog.as_raw() as usize, modal_window.as_raw() as usize);
+    }
+
+    #[test]
+    #[ignore]
+    fn show_through_deref() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+
+        let modal_window: &ModalWindow = &dialog;
+        let result = modal_window.show(None);
+        println!("show through deref: {:?}", result);
+    }
+
+    #[test]
+    #[ignore]
+    fn show_optional() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+
+        let shown = dialog.show_optional(None).expect("failed to show dialog");
+        println!("selected an item: {}", shown);
+    }
+
+    #[test]
+    #[ignore]
+    fn close_from_events() {
+        struct CloseOnOpen;
+
+        impl FileDialogEvents for CloseOnOpen {
+            fn on_folder_change(&self, dialog: &FileDialog) {
+                dialog
+                    .close(HRESULT_FROM_WIN32(ERROR_CANCELLED))
+                    .expect("failed to close dialog");
+            }
+        }
+
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+        dialog.advise(CloseOnOpen).expect("failed to advise");
+
+        let shown = dialog.show_optional(None).expect("failed to show dialog");
+        assert!(!shown);
+    }
+
+    #[test]
+    fn customize_controls() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileSaveDialog::new().expect("failed to create dialog");
+        let customize = dialog.customize().expect("failed to get customize");
+
+        let text = CWideString::new("Exporting a copy").expect("invalid c wide string");
+        customize.add_text(1, &text).expect("failed to add text");
+        customize.add_separator(2).expect("failed to add separator");
+    }
+
+    #[test]
+    fn add_place() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+        let item = ShellItem::from_known_folder(KnownFolder::Downloads)
+            .expect("failed to make shell item");
+        dialog.add_place(&item, true).expect("failed to add place");
+    }
+
+    #[test]
+    fn set_filetypes_empty() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+
+        dialog
+            .set_filetypes(&FileFilters::new())
+            .expect("failed to set empty file types");
+
+        // The empty list was not passed to Windows, so file types can still be set.
+        let mut filters = FileFilters::new();
+        filters
+            .add_extension("txt", None)
+            .expect("failed to add extension");
+        dialog
+            .set_filetypes(&filters)
+            .expect("failed to set file types");
+    }
+
+    #[test]
+    fn update_filetypes() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+
+        let mut filters = FileFilters::new();
+        filters
+            .add_extension("txt", None)
+            .expect("failed to add extension");
+        assert!(dialog
+            .update_filetypes(&filters)
+            .expect("failed to update file types"));
+
+        filters
+            .add_extension("png", None)
+            .expect("failed to add extension");
+        dialog
+            .update_filetypes(&filters)
+            .expect("failed to update file types");
+    }
+
+    #[test]
+    fn display_name_type_round_trip() {
+        let all = [
+            DisplayNameType::NormalDisplay,
+            DisplayNameType::ParentRelativeParsing,
+            DisplayNameType::DesktopAbsoluteParsing,
+            DisplayNameType::ParentRelativeEditing,
+            DisplayNameType::DesktopAbsoluteEditing,
+            DisplayNameType::FileSysPath,
+            DisplayNameType::Url,
+            DisplayNameType::ParentRelativeForAddressBar,
+            DisplayNameType::ParentRelative,
+            DisplayNameType::ParentRelativeForUi,
+        ];
+        for &dnt in all.iter() {
+            assert_eq!(DisplayNameType::try_from(SIGDN::from(dnt)), Ok(dnt));
+        }
+
+        assert_eq!(
+            DisplayNameType::try_from(0x1234),
+            Err(UnknownDisplayNameType(0x1234))
+        );
+    }
+
+    #[test]
+    fn shell_item_from_parsing_name_with_flags() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let path = CWideString::new("C:\\Windows").expect("invalid c wide string");
+        let flags = ParseFlags {
+            prefer_folder_browsing: true,
+            dont_parse_relative: true,
+            translate_aliases: true,
+        };
+        let item = ShellItem::from_parsing_name_with_flags(&path, flags)
+            .expect("failed to create shell item");
+        let name = item
+            .get_display_name(DisplayNameType::FileSysPath)
+            .expect("failed to get display name");
+        assert!(name
+            .as_os_string()
+            .to_string_lossy()
+            .eq_ignore_ascii_case("C:\\Windows"));
+
+        assert!(ParseFlags::default()
+            .bind_ctx()
+            .expect("failed to make bind ctx")
+            .is_none());
+    }
+
+    #[test]
+    fn enumerate_drives_has_system_drive() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let system_drive = std::env::var("SystemDrive").expect("missing system drive");
+        let drives = enumerate_drives().expect("failed to enumerate drives");
+        assert!(drives.iter().any(|drive| {
+            drive
+                .get_display_name(DisplayNameType::FileSysPath)
+                .map(|name| {
+                    name.as_os_string()
+                        .to_string_lossy()
+                        .starts_with(&system_drive)
+                })
+                .unwrap_or(false)
+        }));
+    }
+
+    #[test]
+    fn shell_item_stream_round_trip() {
+        use std::io::Read;
+        use std::io::Write;
+
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let path = std::env::temp_dir().join("win-nfd-shell-item-stream.txt");
+        std::fs::write(&path, "").expect("failed to create file");
+        let item = ShellItem::from_path(&path).expect("failed to create shell item");
+
+        let mut stream = item
+            .bind_to_stream(STGM_WRITE | STGM_CREATE)
+            .expect("failed to bind to stream");
+        stream.write_all(b"hello").expect("failed to write");
+        stream.flush().expect("failed to flush");
+        drop(stream);
+
+        let mut stream = item.bind_to_stream(0).expect("failed to bind to stream");
+        let mut contents = String::new();
+        stream
+            .read_to_string(&mut contents)
+            .expect("failed to read");
+        drop(stream);
+        assert_eq!(contents, "hello");
+
+        std::fs::remove_file(&path).expect("failed to remove file");
+    }
+
+    #[test]
+    fn stream_io_error_keeps_hresult() {
+        use winapi::shared::winerror::E_ACCESSDENIED;
+
+        let error = stream_io_error(E_ACCESSDENIED);
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            error
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<HResult>())
+                .map(|hr| hr.0),
+            Some(E_ACCESSDENIED)
+        );
+    }
+
+    #[test]
+    fn path_is_resolvable() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        assert!(ShellItem::path_is_resolvable(Path::new("Cargo.toml")));
+        assert!(!ShellItem::path_is_resolvable(Path::new(
+            "this-path-does-not-exist.toml"
+        )));
+    }
+
+    #[test]
+    fn shell_item_from_file() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let file = File::open("Cargo.toml").expect("failed to open file");
+        let item = ShellItem::from_file(&file).expect("failed to create shell item");
+        let path = item
+            .get_display_name(DisplayNameType::FileSysPath)
+            .expect("failed to get display name");
+        let path = PathBuf::from(path.as_os_string());
+        assert!(!path.as_os_str().to_string_lossy().starts_with("\\\\?\\"));
+        assert_eq!(
+            path.canonicalize().expect("failed to canonicalize"),
+            Path::new("Cargo.toml")
+                .canonicalize()
+                .expect("failed to canonicalize")
+        );
+    }
+
+    #[test]
+    fn shell_item_best_display_name() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let item = ShellItem::from_path(Path::new("Cargo.toml")).expect("failed to create item");
+        let path = item
+            .get_display_name(DisplayNameType::FileSysPath)
+            .expect("failed to get file system path");
+        assert_eq!(
+            item.best_display_name()
+                .expect("failed to get display name"),
+            path.as_os_string().to_string_lossy()
+        );
+
+        // "This PC" has no file system path.
+        let this_pc = CWideString::new("::{20D04FE0-3AEA-1069-A2D8-08002B30309D}")
+            .expect("invalid c wide string");
+        let item = ShellItem::from_parsing_name(&this_pc).expect("failed to create item");
+        assert!(!item
+            .best_display_name()
+            .expect("failed to get display name")
+            .is_empty());
+    }
+
+    #[test]
+    fn shell_item_debug() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        // The extension may be hidden, depending on Explorer settings.
+        let item = ShellItem::from_path(Path::new("Cargo.toml")).expect("failed to create item");
+        let debug = format!("{:?}", item);
+        assert!(debug.starts_with("ShellItem(\"Cargo"), "{}", debug);
+    }
+
+    #[test]
+    fn shell_item_children() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let folder = ShellItem::from_path(Path::new(".")).expect("failed to create item");
+        let paths = folder
+            .children()
+            .expect("failed to enumerate children")
+            .map(|child| {
+                let child = child.expect("failed to get child");
+                let path = child
+                    .get_display_name(DisplayNameType::FileSysPath)
+                    .expect("failed to get path");
+                PathBuf::from(path.as_os_string())
+            })
+            .collect::<Vec<_>>();
+        assert!(paths.iter().any(|path| path.ends_with("Cargo.toml")));
+
+        let file = ShellItem::from_path(Path::new("Cargo.toml")).expect("failed to create item");
+        assert!(file.children().is_err());
+    }
+
+    #[test]
+    fn shell_item_pidl_bytes_round_trip() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let item = ShellItem::from_path(Path::new("Cargo.toml")).expect("failed to create item");
+        let bytes = item.to_pidl_bytes().expect("failed to serialize pidl");
+        assert!(is_valid_id_list(&bytes));
+
+        let restored = ShellItem::from_pidl_bytes(&bytes).expect("failed to restore item");
+        let path = |item: &ShellItem| {
+            let path = item
+                .get_display_name(DisplayNameType::FileSysPath)
+                .expect("failed to get path");
+            PathBuf::from(path.as_os_string())
+        };
+        assert_eq!(path(&restored), path(&item));
+
+        let error = ShellItem::from_pidl_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(error.0, E_INVALIDARG);
+    }
+
+    #[test]
+    fn valid_id_list() {
+        assert!(is_valid_id_list(&[0, 0]));
+        assert!(is_valid_id_list(&[4, 0, 1, 2, 0, 0]));
+        assert!(!is_valid_id_list(&[]));
+        assert!(!is_valid_id_list(&[4, 0, 1, 2]));
+        assert!(!is_valid_id_list(&[8, 0, 1, 2, 0, 0]));
+        assert!(!is_valid_id_list(&[1, 0, 0, 0]));
+        assert!(!is_valid_id_list(&[0, 0, 0]));
+    }
+
+    #[test]
+    fn shell_item_name() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let item = ShellItem::from_path(Path::new("src")).expect("failed to make shell item");
+        assert_eq!(item.name().expect("failed to get name"), "src");
+    }
+
+    #[test]
+    fn shell_item_url() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let item =
+            ShellItem::from_path(Path::new("Cargo.toml")).expect("failed to make shell item");
+        let url = item.url().expect("failed to get url").expect("missing url");
+        assert!(url.starts_with("file:"));
+        assert!(url.ends_with("Cargo.toml"));
+    }
+
+    #[test]
+    fn try_get_result_without_showing() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+        let result = dialog.try_get_result().expect("failed to get result");
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn file_filters_from_raw() {
+        let name: &'static CWideString = Box::leak(Box::new(
+            CWideString::new("Text").expect("invalid c wide string"),
+        ));
+        let spec: &'static CWideString = Box::leak(Box::new(
+            CWideString::new("*.txt").expect("invalid c wide string"),
+        ));
+        let specs: &'static [COMDLG_FILTERSPEC] = Box::leak(Box::new([COMDLG_FILTERSPEC {
+            pszName: name.as_ptr(),
+            pszSpec: spec.as_ptr(),
+        }]));
+
+        let filters = unsafe { FileFilters::from_raw(specs) };
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters.as_slice()[0].pszName, name.as_ptr());
+        assert_eq!(filters.as_slice()[0].pszSpec, spec.as_ptr());
+
+        let specs: Vec<_> = filters.specs().collect();
+        assert_eq!(specs, [spec.as_c_wide_str()]);
+
+        let cloned = filters.clone();
+        assert_eq!(cloned.len(), 1);
+    }
+
+    #[test]
+    fn file_filters_from_pairs() {
+        let filters =
+            FileFilters::from_pairs(&[("Text Files", "*.txt"), ("Images", "*.png;*.jpg")])
+                .expect("failed to make filters");
+
+        assert_eq!(filters.len(), 2);
+        for (spec, (name, filter)) in filters.as_slice().iter().zip(filters.storage.iter()) {
+            assert_eq!(spec.pszName, name.as_ptr());
+            assert_eq!(spec.pszSpec, filter.as_ptr());
+        }
+        assert_eq!(
+            filters.storage[0].0.as_slice(),
+            CWideString::new("Text Files").unwrap().as_slice()
+        );
+        assert_eq!(
+            filters.storage[1].1.as_slice(),
+            CWideString::new("*.png;*.jpg").unwrap().as_slice()
+        );
+
+        assert!(FileFilters::from_pairs(&[("Text\0Files", "*.txt")]).is_err());
+    }
+
+    #[test]
+    fn file_filters_add_no_extensions() {
+        let mut filters = FileFilters::new();
+        filters
+            .add_extensions(&[], None)
+            .expect("failed to add extensions");
+        filters
+            .add_extensions(&[], Some("Images"))
+            .expect("failed to add extensions");
+
+        assert_eq!(filters.len(), 0);
+    }
+
+    #[test]
+    fn file_filters_add_extensions() {
+        let mut filters = FileFilters::new();
+        filters
+            .add_extension("png", None)
+            .expect("failed to add extension");
+        filters
+            .add_extensions(&["png", ".jpg"], Some("Images"))
+            .expect("failed to add extensions");
+
+        assert_eq!(filters.len(), 2);
+        assert_eq!(
+            filters.storage[0].0.as_slice(),
+            CWideString::new("PNG Files (*.png)").unwrap().as_slice()
+        );
+        assert_eq!(
+            filters.storage[0].1.as_slice(),
+            CWideString::new("*.png").unwrap().as_slice()
+        );
+        assert_eq!(
+            filters.storage[1].0.as_slice(),
+            CWideString::new("Images").unwrap().as_slice()
+        );
+        assert_eq!(
+            filters.storage[1].1.as_slice(),
+            CWideString::new("*.png;*.jpg").unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn file_filters_add_known() {
+        let mut filters = FileFilters::new();
+        filters.add_known("txt").expect("failed to add filter");
+        filters.add_known(".PNG").expect("failed to add filter");
+        filters.add_known("sks").expect("failed to add filter");
+
+        let names: Vec<String> = filters
+            .storage
+            .iter()
+            .map(|(name, _)| String::from_utf16_lossy(name.as_slice()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Text Documents (*.txt)",
+                "PNG Images (*.PNG)",
+                "SKS Files (*.sks)"
+            ]
+        );
+    }
+
+    #[test]
+    fn file_filters_clone() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+
+        let mut filters = FileFilters::new();
+        filters
+            .add_extension("toml", None)
+            .expect("failed to add extension");
+        let cloned = filters.clone();
+
+        assert_eq!(cloned.len(), filters.len());
+        assert_ne!(cloned.filters[0].pszName, filters.filters[0].pszName);
+        assert_ne!(cloned.filters[0].pszSpec, filters.filters[0].pszSpec);
+        assert_eq!(cloned.filters[0].pszName, cloned.storage[0].0.as_ptr());
+        assert_eq!(cloned.filters[0].pszSpec, cloned.storage[0].1.as_ptr());
+
+        drop(filters);
+
+        let dialog = FileOpenDialog::new().expect("failed to create dialog");
+        dialog
+            .set_filetypes(&cloned)
+            .expect("failed to set filetypes");
+    }
+
+    #[test]
+    fn file_filters_reject_empty() {
+        let name = CWideString::new("Text").expect("invalid c wide string");
+        let empty = CWideString::new("").expect("invalid c wide string");
+
+        let mut filters = FileFilters::new();
+        assert_eq!(
+            filters.try_add_filter(Cow::Borrowed(&*name), Cow::Borrowed(&*empty)),
+            Err(FileFilterError::EmptySpec)
+        );
+        assert_eq!(
+            filters.try_add_filter(Cow::Borrowed(&*empty), Cow::Borrowed(&*name)),
+            Err(FileFilterError::EmptyName)
+        );
+        assert!(filters.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn file_filters_add_empty_spec_debug_asserts() {
+        let name = CWideString::new("Text").expect("invalid c wide string");
+        let empty = CWideString::new("").expect("invalid c wide string");
+
+        let mut filters = FileFilters::new();
+        filters.add_filter(Cow::Borrowed(&*name), Cow::Borrowed(&*empty));
+    }
+
+    #[test]
+    fn shell_folder_children() {
+        skylight::init_mta_com_runtime().expect("failed to init com");
+        let item = ShellItem::from_path(".".as_ref()).expect("failed to make shell item");
+        let folder = item.bind_to_storage().expect("failed to bind to storage");
+        let children = folder.children().expect("failed to get children");
+        let has_cargo_toml = children.iter().any(|child| {
+            let path = child
+                .get_display_name(DisplayNameType::FileSysPath)
+                .expect("failed to get path");
+            Path::new(&path.as_os_string()).ends_with("Cargo.toml")
+        });
+        assert!(has_cargo_toml);
+    }
+
+    #[test]
+    fn filter_count_conversion() {
+        assert_eq!(filter_count(0).ok(), Some(0));
+        assert_eq!(filter_count(2).ok(), Some(2));
+        assert_eq!(filter_count(u32::MAX as usize).ok(), Some(u32::MAX));
+
+        #[cfg(target_pointer_width = "64")]
+        assert!(filter_count(u32::MAX as usize + 1).is_err());
+    }
 }
@@ -0,0 +1,206 @@
+use super::FileDialog;
+use std::mem::ManuallyDrop;
+use std::panic::AssertUnwindSafe;
+use std::ptr::NonNull;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::IsEqualGUID;
+use winapi::shared::guiddef::REFIID;
+use winapi::shared::minwindef::ULONG;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror::E_FAIL;
+use winapi::shared::winerror::E_NOINTERFACE;
+use winapi::shared::winerror::E_NOTIMPL;
+use winapi::shared::winerror::S_FALSE;
+use winapi::shared::winerror::S_OK;
+use winapi::um::shobjidl::IFileDialog;
+use winapi::um::shobjidl::IFileDialogEvents;
+use winapi::um::shobjidl::IFileDialogEventsVtbl;
+use winapi::um::shobjidl::FDE_OVERWRITE_RESPONSE;
+use winapi::um::shobjidl::FDE_SHAREVIOLATION_RESPONSE;
+use winapi::um::shobjidl_core::IShellItem;
+use winapi::um::unknwnbase::IUnknown;
+use winapi::um::unknwnbase::IUnknownVtbl;
+use winapi::Interface;
+
+/// Callbacks for events that occur while a [`FileDialog`] is shown.
+///
+/// Every method has a default implementation that does nothing.
+pub trait FileDialogEvents {
+    /// Called just before the dialog returns a result.
+    ///
+    /// Return `false` to keep the dialog open.
+    fn on_file_ok(&self, _dialog: &FileDialog) -> bool {
+        true
+    }
+
+    /// Called when the user navigates to a new folder.
+    fn on_folder_change(&self, _dialog: &FileDialog) {}
+
+    /// Called when the selection changes.
+    fn on_selection_change(&self, _dialog: &FileDialog) {}
+
+    /// Called when the selected file type changes.
+    ///
+    /// This is also called once when the dialog opens.
+    fn on_type_change(&self, _dialog: &FileDialog) {}
+}
+
+/// An `IFileDialogEvents` implementation that forwards to a [`FileDialogEvents`].
+///
+/// The vtable must be the first field so that a pointer to this is also a valid `IFileDialogEvents` pointer.
+#[repr(C)]
+struct EventSink {
+    vtbl: *const IFileDialogEventsVtbl,
+    ref_count: AtomicU32,
+    events: Box<dyn FileDialogEvents>,
+}
+
+static VTBL: IFileDialogEventsVtbl = IFileDialogEventsVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: query_interface,
+        AddRef: add_ref,
+        Release: release,
+    },
+    OnFileOk: on_file_ok,
+    OnFolderChanging: on_folder_changing,
+    OnFolderChange: on_folder_change,
+    OnSelectionChange: on_selection_change,
+    OnShareViolation: on_share_violation,
+    OnTypeChange: on_type_change,
+    OnOverwrite: on_overwrite,
+};
+
+/// Make a new `IFileDialogEvents` that forwards to the given events.
+///
+/// The returned pointer owns one reference.
+pub(super) fn new(events: Box<dyn FileDialogEvents>) -> NonNull<IFileDialogEvents> {
+    let object = Box::new(EventSink {
+        vtbl: &VTBL,
+        ref_count: AtomicU32::new(1),
+        events,
+    });
+
+    NonNull::from(Box::leak(object)).cast()
+}
+
+/// Call a callback with a borrowed dialog, converting panics into `E_FAIL`.
+unsafe fn dispatch<F>(this: *mut IFileDialogEvents, pfd: *mut IFileDialog, f: F) -> HRESULT
+where
+    F: FnOnce(&dyn FileDialogEvents, &FileDialog) -> HRESULT,
+{
+    let this = &*(this as *const EventSink);
+    let pfd = match NonNull::new(pfd) {
+        Some(pfd) => pfd,
+        None => return S_OK,
+    };
+
+    // The dialog is borrowed from the caller, so it must not be released.
+    let dialog = ManuallyDrop::new(FileDialog(pfd));
+    std::panic::catch_unwind(AssertUnwindSafe(|| f(&*this.events, &dialog))).unwrap_or(E_FAIL)
+}
+
+unsafe extern "system" fn query_interface(
+    this: *mut IUnknown,
+    riid: REFIID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if IsEqualGUID(&*riid, &IUnknown::uuidof()) || IsEqualGUID(&*riid, &IFileDialogEvents::uuidof())
+    {
+        add_ref(this);
+        *ppv = this.cast();
+        return S_OK;
+    }
+
+    *ppv = std::ptr::null_mut();
+    E_NOINTERFACE
+}
+
+unsafe extern "system" fn add_ref(this: *mut IUnknown) -> ULONG {
+    let this = &*(this as *const EventSink);
+    this.ref_count.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+unsafe extern "system" fn release(this: *mut IUnknown) -> ULONG {
+    let ref_count = (*(this as *const EventSink))
+        .ref_count
+        .fetch_sub(1, Ordering::Release)
+        - 1;
+
+    if ref_count == 0 {
+        std::sync::atomic::fence(Ordering::Acquire);
+        drop(Box::from_raw(this as *mut EventSink));
+    }
+
+    ref_count
+}
+
+unsafe extern "system" fn on_file_ok(
+    this: *mut IFileDialogEvents,
+    pfd: *mut IFileDialog,
+) -> HRESULT {
+    dispatch(this, pfd, |events, dialog| {
+        if events.on_file_ok(dialog) {
+            S_OK
+        } else {
+            S_FALSE
+        }
+    })
+}
+
+unsafe extern "system" fn on_folder_changing(
+    _this: *mut IFileDialogEvents,
+    _pfd: *mut IFileDialog,
+    _psi_folder: *mut IShellItem,
+) -> HRESULT {
+    E_NOTIMPL
+}
+
+unsafe extern "system" fn on_folder_change(
+    this: *mut IFileDialogEvents,
+    pfd: *mut IFileDialog,
+) -> HRESULT {
+    dispatch(this, pfd, |events, dialog| {
+        events.on_folder_change(dialog);
+        S_OK
+    })
+}
+
+unsafe extern "system" fn on_selection_change(
+    this: *mut IFileDialogEvents,
+    pfd: *mut IFileDialog,
+) -> HRESULT {
+    dispatch(this, pfd, |events, dialog| {
+        events.on_selection_change(dialog);
+        S_OK
+    })
+}
+
+unsafe extern "system" fn on_share_violation(
+    _this: *mut IFileDialogEvents,
+    _pfd: *mut IFileDialog,
+    _psi: *mut IShellItem,
+    _p_response: *mut FDE_SHAREVIOLATION_RESPONSE,
+) -> HRESULT {
+    E_NOTIMPL
+}
+
+unsafe extern "system" fn on_type_change(
+    this: *mut IFileDialogEvents,
+    pfd: *mut IFileDialog,
+) -> HRESULT {
+    dispatch(this, pfd, |events, dialog| {
+        events.on_type_change(dialog);
+        S_OK
+    })
+}
+
+unsafe extern "system" fn on_overwrite(
+    _this: *mut IFileDialogEvents,
+    _pfd: *mut IFileDialog,
+    _psi: *mut IShellItem,
+    _p_response: *mut FDE_OVERWRITE_RESPONSE,
+) -> HRESULT {
+    E_NOTIMPL
+}
@@ -0,0 +1,121 @@
+use super::ShellItem;
+use std::mem::ManuallyDrop;
+use std::panic::AssertUnwindSafe;
+use std::ptr::NonNull;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::IsEqualGUID;
+use winapi::shared::guiddef::REFIID;
+use winapi::shared::minwindef::ULONG;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror::E_FAIL;
+use winapi::shared::winerror::E_NOINTERFACE;
+use winapi::shared::winerror::E_NOTIMPL;
+use winapi::shared::winerror::S_FALSE;
+use winapi::shared::winerror::S_OK;
+use winapi::um::shobjidl_core::IShellItem;
+use winapi::um::shobjidl_core::IShellItemFilter;
+use winapi::um::shobjidl_core::IShellItemFilterVtbl;
+use winapi::um::shobjidl_core::SHCONTF;
+use winapi::um::unknwnbase::IUnknown;
+use winapi::um::unknwnbase::IUnknownVtbl;
+use winapi::Interface;
+
+/// The closure called for each item
+pub(super) type Filter = Box<dyn Fn(&ShellItem) -> bool>;
+
+/// An `IShellItemFilter` implementation that calls a closure.
+///
+/// The vtable must be the first field so that a pointer to this is also a valid `IShellItemFilter` pointer.
+#[repr(C)]
+struct ShellItemFilter {
+    vtbl: *const IShellItemFilterVtbl,
+    ref_count: AtomicU32,
+    filter: Filter,
+}
+
+static VTBL: IShellItemFilterVtbl = IShellItemFilterVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: query_interface,
+        AddRef: add_ref,
+        Release: release,
+    },
+    IncludeItem: include_item,
+    GetEnumFlagsForItem: get_enum_flags_for_item,
+};
+
+/// Make a new `IShellItemFilter` that calls the given closure.
+///
+/// The returned pointer owns one reference.
+pub(super) fn new(filter: Filter) -> NonNull<IShellItemFilter> {
+    let object = Box::new(ShellItemFilter {
+        vtbl: &VTBL,
+        ref_count: AtomicU32::new(1),
+        filter,
+    });
+
+    NonNull::from(Box::leak(object)).cast()
+}
+
+unsafe extern "system" fn query_interface(
+    this: *mut IUnknown,
+    riid: REFIID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if IsEqualGUID(&*riid, &IUnknown::uuidof()) || IsEqualGUID(&*riid, &IShellItemFilter::uuidof())
+    {
+        add_ref(this);
+        *ppv = this.cast();
+        return S_OK;
+    }
+
+    *ppv = std::ptr::null_mut();
+    E_NOINTERFACE
+}
+
+unsafe extern "system" fn add_ref(this: *mut IUnknown) -> ULONG {
+    let this = &*(this as *const ShellItemFilter);
+    this.ref_count.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+unsafe extern "system" fn release(this: *mut IUnknown) -> ULONG {
+    let ref_count = (*(this as *const ShellItemFilter))
+        .ref_count
+        .fetch_sub(1, Ordering::Release)
+        - 1;
+
+    if ref_count == 0 {
+        std::sync::atomic::fence(Ordering::Acquire);
+        drop(Box::from_raw(this as *mut ShellItemFilter));
+    }
+
+    ref_count
+}
+
+unsafe extern "system" fn include_item(
+    this: *mut IShellItemFilter,
+    psi: *mut IShellItem,
+) -> HRESULT {
+    let this = &*(this as *const ShellItemFilter);
+    let psi = match NonNull::new(psi) {
+        Some(psi) => psi,
+        None => return S_OK,
+    };
+
+    // The item is borrowed from the caller, so it must not be released.
+    let item = ManuallyDrop::new(ShellItem(psi));
+    match std::panic::catch_unwind(AssertUnwindSafe(|| (this.filter)(&item))) {
+        Ok(true) => S_OK,
+        Ok(false) => S_FALSE,
+        Err(_) => E_FAIL,
+    }
+}
+
+unsafe extern "system" fn get_enum_flags_for_item(
+    _this: *mut IShellItemFilter,
+    _psi: *mut IShellItem,
+    _pgrf_flags: *mut SHCONTF,
+) -> HRESULT {
+    E_NOTIMPL
+}
@@ -0,0 +1,12 @@
+use winapi::um::minwinbase::SYSTEMTIME;
+use winapi::um::sysinfoapi::GetLocalTime;
+
+/// Get the current local date and time.
+pub(crate) fn get_local_time() -> SYSTEMTIME {
+    let mut time = unsafe { std::mem::zeroed() };
+    unsafe {
+        GetLocalTime(&mut time);
+    }
+
+    time
+}
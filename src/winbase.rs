@@ -0,0 +1,108 @@
+use skylight::HResult;
+use winapi::shared::winerror::HRESULT_FROM_WIN32;
+use winapi::um::winbase::FormatMessageW;
+use winapi::um::winbase::FORMAT_MESSAGE_FROM_SYSTEM;
+use winapi::um::winbase::FORMAT_MESSAGE_IGNORE_INSERTS;
+
+/// Get the system message for an error code, like "The system cannot find the file specified." for `0x80070002`.
+///
+/// Returns `None` if Windows has no message for the code.
+pub fn format_message(code: u32) -> Option<String> {
+    let mut buffer = [0_u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            std::ptr::null(),
+            code,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if len == 0 {
+        return None;
+    }
+
+    // Messages end with a newline.
+    let message = String::from_utf16_lossy(&buffer[..len as usize]);
+    let message = message.trim_end();
+    if message.is_empty() {
+        return None;
+    }
+
+    Some(message.into())
+}
+
+/// Describe an [`HResult`] with its system message, falling back to the code if there is none.
+pub(crate) fn describe_hresult(hr: &HResult) -> String {
+    match format_message(hr.0 as u32) {
+        Some(message) => format!("{} (0x{:08X})", message, hr.0),
+        None => hr.to_string(),
+    }
+}
+
+/// A raw Win32 error code, like `1008` for `ERROR_NO_TOKEN`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Win32Error(u32);
+
+impl Win32Error {
+    /// Make a [`Win32Error`] from a raw error code.
+    pub fn new(code: u32) -> Self {
+        Self(code)
+    }
+
+    /// Get the last error of the current thread.
+    pub fn last_error() -> Self {
+        let code = std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+        Self(code as u32)
+    }
+
+    /// Get the raw error code.
+    pub fn last_error_code(&self) -> u32 {
+        self.0
+    }
+
+    /// Wrap this in an [`HResult`], like `0x800703F0` for `1008`.
+    pub fn to_hresult(self) -> HResult {
+        HResult::from(HRESULT_FROM_WIN32(self.0))
+    }
+}
+
+impl std::fmt::Display for Win32Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match format_message(self.0) {
+            Some(message) => write!(f, "{} ({})", message, self.0),
+            None => write!(f, "Win32 error {}", self.0),
+        }
+    }
+}
+
+impl std::error::Error for Win32Error {}
+
+impl From<Win32Error> for HResult {
+    fn from(error: Win32Error) -> Self {
+        error.to_hresult()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_file_not_found() {
+        let message = format_message(0x8007_0002).expect("missing message");
+        assert!(!message.is_empty());
+        assert!(!message.ends_with('\n'));
+    }
+
+    #[test]
+    fn win32_error() {
+        let error = Win32Error::new(2);
+        assert_eq!(error.last_error_code(), 2);
+        assert_eq!(error.to_hresult().0, 0x8007_0002_u32 as i32);
+        assert!(error.to_string().ends_with("(2)"));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::FileDialog;
+use crate::Win32Error;
+use std::os::windows::ffi::OsStrExt;
+use winapi::shared::minwindef::LPARAM;
+use winapi::shared::minwindef::LRESULT;
+use winapi::shared::minwindef::UINT;
+use winapi::shared::minwindef::WPARAM;
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::ERROR_CANCELLED;
+use winapi::shared::winerror::ERROR_CLASS_ALREADY_EXISTS;
+use winapi::shared::winerror::HRESULT_FROM_WIN32;
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::winuser::CreateWindowExW;
+use winapi::um::winuser::DefWindowProcW;
+use winapi::um::winuser::DestroyWindow;
+use winapi::um::winuser::FindWindowExW;
+use winapi::um::winuser::GetDlgItem;
+use winapi::um::winuser::GetWindowLongPtrW;
+use winapi::um::winuser::IsWindow;
+use winapi::um::winuser::PostMessageW;
+use winapi::um::winuser::RegisterClassW;
+use winapi::um::winuser::SetWindowLongPtrW;
+use winapi::um::winuser::EM_SETSEL;
+use winapi::um::winuser::GWLP_USERDATA;
+use winapi::um::winuser::HWND_MESSAGE;
+use winapi::um::winuser::WM_APP;
+use winapi::um::winuser::WNDCLASSW;
+
+/// The control id of the filename combo box in a file dialog
+const CMB13: i32 = 0x047C;
+
+/// Find the filename edit control of a shown file dialog.
+///
+/// This relies on the undocumented layout of the dialog, so it may not work on all versions of Windows.
+pub(crate) fn find_filename_edit(dialog: HWND) -> Option<HWND> {
+    let combo = unsafe { GetDlgItem(dialog, CMB13) };
+    if combo.is_null() {
+        return None;
+    }
+
+    // The edit control is a child of the combo box.
+    // On some versions, the combo box is itself wrapped in another combo box.
+    let class: Vec<u16> = std::ffi::OsStr::new("Edit")
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut parent = combo;
+    for _ in 0..2 {
+        let edit = unsafe {
+            FindWindowExW(
+                parent,
+                std::ptr::null_mut(),
+                class.as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        if !edit.is_null() {
+            return Some(edit);
+        }
+
+        let child = unsafe {
+            FindWindowExW(
+                parent,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        if child.is_null() {
+            return None;
+        }
+        parent = child;
+    }
+
+    None
+}
+
+/// Select a range of wide chars in an edit control.
+///
+/// The selection is posted, so it happens after the control finishes processing its pending messages.
+pub(crate) fn post_select_text(edit: HWND, start: usize, end: usize) -> bool {
+    unsafe { PostMessageW(edit, EM_SETSEL, start as WPARAM, end as LPARAM) != 0 }
+}
+
+/// Check if a handle identifies an existing window.
+pub(crate) fn is_window(window: HWND) -> bool {
+    unsafe { IsWindow(window) != 0 }
+}
+
+/// The message that asks a [`DialogCloser`] to close its dialog
+const WM_CLOSE_DIALOG: UINT = WM_APP;
+
+/// A message-only window that closes a file dialog shown on the same thread when it receives [`WM_CLOSE_DIALOG`].
+///
+/// A shown dialog may only be used from its own thread,
+/// so other threads post a message to this window instead,
+/// and the modal loop of the dialog dispatches it on the dialog's thread.
+pub(crate) struct DialogCloser(HWND);
+
+impl DialogCloser {
+    /// Make a new [`DialogCloser`] for a dialog on the current thread.
+    ///
+    /// # Safety
+    /// The dialog must not be moved or dropped while the returned closer is alive.
+    pub(crate) unsafe fn new(dialog: &FileDialog) -> Result<Self, Win32Error> {
+        let class: Vec<u16> = std::ffi::OsStr::new("win-nfd-dialog-closer")
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let instance = GetModuleHandleW(std::ptr::null());
+
+        let mut wnd_class: WNDCLASSW = std::mem::zeroed();
+        wnd_class.lpfnWndProc = Some(dialog_closer_proc);
+        wnd_class.hInstance = instance;
+        wnd_class.lpszClassName = class.as_ptr();
+
+        // The class stays registered for the life of the process, so only the first call registers it.
+        if RegisterClassW(&wnd_class) == 0 {
+            let error = Win32Error::last_error();
+            if error.last_error_code() != ERROR_CLASS_ALREADY_EXISTS {
+                return Err(error);
+            }
+        }
+
+        let window = CreateWindowExW(
+            0,
+            class.as_ptr(),
+            std::ptr::null(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            HWND_MESSAGE,
+            std::ptr::null_mut(),
+            instance,
+            std::ptr::null_mut(),
+        );
+        if window.is_null() {
+            return Err(Win32Error::last_error());
+        }
+        SetWindowLongPtrW(window, GWLP_USERDATA, dialog as *const FileDialog as _);
+
+        Ok(Self(window))
+    }
+
+    /// Get the handle of the message-only window.
+    pub(crate) fn window(&self) -> HWND {
+        self.0
+    }
+}
+
+impl Drop for DialogCloser {
+    fn drop(&mut self) {
+        unsafe {
+            DestroyWindow(self.0);
+        }
+    }
+}
+
+/// Ask the [`DialogCloser`] with the given window to close its dialog.
+///
+/// This may be called from any thread.
+/// The dialog is closed once its thread dispatches the message, which only happens while the dialog is shown.
+pub(crate) fn post_close_dialog(closer: HWND) -> bool {
+    unsafe { PostMessageW(closer, WM_CLOSE_DIALOG, 0, 0) != 0 }
+}
+
+/// The window procedure of a [`DialogCloser`].
+unsafe extern "system" fn dialog_closer_proc(
+    window: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg != WM_CLOSE_DIALOG {
+        return DefWindowProcW(window, msg, wparam, lparam);
+    }
+
+    let dialog = GetWindowLongPtrW(window, GWLP_USERDATA) as *const FileDialog;
+    if let Some(dialog) = dialog.as_ref() {
+        if let Err(_e) = dialog.close(HRESULT_FROM_WIN32(ERROR_CANCELLED)) {
+            debug!("Close failed: {}", _e);
+        }
+    }
+
+    0
+}